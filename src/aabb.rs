@@ -0,0 +1,126 @@
+use crate::{Point3, Ray, Vec3};
+
+// axis-aligned bounding box, stored as its two extreme corners
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    minimum: Point3,
+    maximum: Point3,
+}
+
+impl Aabb {
+    pub fn new(minimum: Point3, maximum: Point3) -> Self {
+        Self { minimum, maximum }
+    }
+
+    pub fn min(&self) -> Point3 {
+        self.minimum
+    }
+
+    pub fn max(&self) -> Point3 {
+        self.maximum
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    // index of the axis along which the box is widest
+    pub fn longest_axis(&self) -> usize {
+        let d = self.maximum - self.minimum;
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    // slab test: the ray hits the box if the t intervals where it lies
+    // between each pair of axis planes overlap within [t_min, t_max]
+    pub fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for axis in 0..3 {
+            let inv_d = 1.0 / r.direction()[axis];
+            let mut t0 = (self.minimum[axis] - r.origin()[axis]) * inv_d;
+            let mut t1 = (self.maximum[axis] - r.origin()[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+
+    // smallest box containing both boxes
+    pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
+        let minimum = Vec3::new(
+            box0.minimum.x().min(box1.minimum.x()),
+            box0.minimum.y().min(box1.minimum.y()),
+            box0.minimum.z().min(box1.minimum.z()),
+        );
+        let maximum = Vec3::new(
+            box0.maximum.x().max(box1.maximum.x()),
+            box0.maximum.y().max(box1.maximum.y()),
+            box0.maximum.z().max(box1.maximum.z()),
+        );
+        Aabb::new(minimum, maximum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Point3::zero(), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn ray_through_box_hits() {
+        let r = Ray::new(Point3::new(0.5, 0.5, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(unit_box().hit(r, 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn ray_pointing_away_misses() {
+        let r = Ray::new(Point3::new(0.5, 0.5, -5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!unit_box().hit(r, 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn ray_beside_box_misses() {
+        let r = Ray::new(Point3::new(2.0, 0.5, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!unit_box().hit(r, 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn hit_respects_t_range() {
+        let r = Ray::new(Point3::new(0.5, 0.5, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!unit_box().hit(r, 0.0, 4.0));
+    }
+
+    #[test]
+    fn surrounding_box_contains_both() {
+        let other = Aabb::new(Point3::new(-1.0, 2.0, 0.5), Point3::new(0.5, 3.0, 0.7));
+        let surrounding = Aabb::surrounding_box(unit_box(), other);
+        assert_eq_vec3s(surrounding.min(), Point3::new(-1.0, 0.0, 0.0));
+        assert_eq_vec3s(surrounding.max(), Point3::new(1.0, 3.0, 1.0));
+    }
+
+    #[test]
+    fn surface_area_of_unit_box() {
+        assert_f64_eq(unit_box().surface_area(), 6.0);
+    }
+}
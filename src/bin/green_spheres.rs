@@ -1,33 +1,41 @@
 use raytracing::{
-    rand_f64, rand_in_range, write_sampled_color, Camera, Color, Dieletric, Hittable, HittableList,
-    Lambertian, Material, Metal, Point3, Ray, Sphere, Vec3,
+    BvhNode, Camera, Color, Csg, Dieletric, HittableList, Lambertian, Material, Metal, PathTracer,
+    Pcg32, Point3, Quad, Renderer, SkyGradient, SobolSampler, Sphere, ToneMapOperator, ToneMapping,
+    Vec3,
+};
+use std::{
+    env,
+    io::{self, BufWriter},
+    sync::Arc,
 };
-use std::rc::Rc;
 
-fn random_scene() -> HittableList {
+fn random_scene(rng: &mut Pcg32) -> HittableList {
     let mut world = HittableList::default();
-    let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    world.add(Rc::new(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Quad::new(
+        Point3::new(-1000.0, 0.0, -1000.0),
+        Vec3::new(0.0, 0.0, 2000.0),
+        Vec3::new(2000.0, 0.0, 0.0),
         ground_material,
     )));
 
-    let metal_mat = Rc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0));
+    let metal_mat = Arc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0));
     let metal_p = Point3::new(-4.0, 1.0, 0.5);
-    world.add(Rc::new(Sphere::new(metal_p, 1.0, metal_mat)));
+    world.add(Arc::new(Sphere::new(metal_p, 1.0, metal_mat)));
 
-    let glass_mat = Rc::new(Dieletric::new(1.5));
+    let glass_mat = Arc::new(Dieletric::new(1.5));
     let glass_p = Point3::new(5.0, 1.0, 0.5);
-    world.add(Rc::new(Sphere::new(glass_p, 1.0, glass_mat.clone())));
-    let glass_hollow = Point3::new(5.0, 1.0, 0.5);
-    world.add(Rc::new(Sphere::new(glass_hollow, -0.9, glass_mat.clone())));
+    // a hollow glass ball: a thin shell around an air-filled cavity
+    world.add(Arc::new(Csg::difference(
+        Arc::new(Sphere::new(glass_p, 1.0, glass_mat.clone())),
+        Arc::new(Sphere::new(glass_p, 0.9, glass_mat.clone())),
+    )));
 
-    let lamber_mat = Rc::new(Lambertian::new(Color::new(0.2, 0.2, 0.2)));
+    let lamber_mat = Arc::new(Lambertian::new(Color::new(0.2, 0.2, 0.2)));
     let lamber_p = Point3::new(-1.0, 1.0, -3.0);
-    world.add(Rc::new(Sphere::new(lamber_p, 1.0, lamber_mat)));
+    world.add(Arc::new(Sphere::new(lamber_p, 1.0, lamber_mat)));
 
-    let colors = vec![
+    let colors = [
         Color::new(0., 75., 35.) / 255.,
         Color::new(0., 100., 0.) / 255.,
         Color::new(0., 114., 0.) / 255.,
@@ -40,11 +48,11 @@ fn random_scene() -> HittableList {
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = rand_f64();
+            let choose_mat = rng.rand_f64();
             let center = Point3::new(
-                a as f64 + 0.7 * rand_f64(),
+                a as f64 + 0.7 * rng.rand_f64(),
                 0.2,
-                b as f64 + 0.7 * rand_f64(),
+                b as f64 + 0.7 * rng.rand_f64(),
             );
 
             let can_spawn = (center - glass_p).length() > 1.2
@@ -52,16 +60,15 @@ fn random_scene() -> HittableList {
                 && (center - metal_p).length() > 1.2;
 
             if can_spawn {
-                let sphere_material: Rc<dyn Material>;
-                if choose_mat < 0.8 {
+                let sphere_material: Arc<dyn Material> = if choose_mat < 0.8 {
                     // diffuse
-                    let albedo = colors[(rand_in_range(0.0, colors.len() as f64)) as usize];
-                    sphere_material = Rc::new(Lambertian::new(albedo));
+                    let albedo = colors[(rng.rand_in_range(0.0, colors.len() as f64)) as usize];
+                    Arc::new(Lambertian::new(albedo))
                 } else {
                     // glass
-                    sphere_material = glass_mat.clone();
-                }
-                world.add(Rc::new(Sphere::new(center, 0.2, sphere_material)));
+                    glass_mat.clone()
+                };
+                world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
             }
         }
     }
@@ -69,22 +76,6 @@ fn random_scene() -> HittableList {
     world
 }
 
-fn ray_color(r: Ray, world: &dyn Hittable, depth: u32) -> Color {
-    if depth == 0 {
-        return Color::zero();
-    }
-    if let Some(rec) = world.hit(r, 0.001, f64::INFINITY) {
-        if let Some(reflectance) = rec.mat_ptr.scatter(r, rec.clone()) {
-            return reflectance.attenuation
-                * ray_color(reflectance.scattered_ray, world, depth - 1);
-        }
-        return Color::zero();
-    }
-    let unit_direction = Vec3::unit(r.direction());
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    ((1.0 - t) * Color::new(1.0, 1.0, 1.0)) + (t * Color::new(0.3, 0.7, 1.0))
-}
-
 fn main() {
     // image
     let aspect_ratio = 3.0 / 2.0;
@@ -110,25 +101,47 @@ fn main() {
         dist_to_focus,
     );
 
-    // PPM image format specifications
-    println!("P3"); // colors are in ascii
-    println!("{} {}", image_width, image_height);
-    println!("{}", 255);
+    // the same seed reproduces both the scene and the render. a random one
+    // is only picked when SEED is unset, never in place of a malformed one
+    let seed = match env::var("SEED") {
+        Err(env::VarError::NotPresent) => rand::random(),
+        seed => seed
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .expect("SEED must be an unsigned integer"),
+    };
+    eprintln!("Seed: {}", seed);
 
     // world
-    let world = random_scene();
+    let world = BvhNode::new(random_scene(&mut Pcg32::new(seed)));
 
-    for y in (0..image_height).rev() {
-        eprintln!("Scanlines remaining: {}", y);
-        for x in 0..image_width {
-            let mut pixel_color = Color::zero();
-            for _s in 0..samples_per_pixel {
-                let x_percent = (x as f64 + rand_f64()) / (image_width as f64);
-                let y_percent = (y as f64 + rand_f64()) / (image_height as f64);
-                let r = cam.get_ray(x_percent, y_percent);
-                pixel_color += ray_color(r, &world, max_depth);
-            }
-            write_sampled_color(pixel_color, samples_per_pixel);
+    let renderer = Renderer::new(cam, image_width, image_height, samples_per_pixel, max_depth)
+        .with_seed(seed)
+        .with_progress(|left| eprintln!("Scanlines remaining: {}", left))
+        .with_sampler(SobolSampler::new());
+    let sky = SkyGradient::new(Color::new(1.0, 1.0, 1.0), Color::new(0.3, 0.7, 1.0));
+    let framebuffer = renderer.render(&world, &PathTracer::new(Arc::new(sky)));
+    // TONE_MAP picks the operator (clamp, reinhard, reinhard:<white>, aces
+    // or hable), ACES by default so bright highlights roll off instead of
+    // clipping, and EXPOSURE brightens or darkens by that many stops
+    let operator = env::var("TONE_MAP")
+        .map(|name| name.parse().unwrap_or_else(|err| panic!("{}", err)))
+        .unwrap_or(ToneMapOperator::Aces);
+    let exposure = env::var("EXPOSURE")
+        .map(|ev| ev.parse().expect("EXPOSURE must be a number of stops"))
+        .unwrap_or(0.0);
+    let tone_mapping = ToneMapping::new(operator).with_exposure(exposure);
+    // an output path picks the format by extension (.16.png for 16 bit
+    // PNG), else PPM goes to stdout
+    match env::args().nth(1) {
+        Some(path) => framebuffer
+            .save(path, &tone_mapping)
+            .expect("failed to save image"),
+        None => {
+            let stdout = io::stdout();
+            framebuffer
+                .write_ppm(BufWriter::new(stdout.lock()), &tone_mapping)
+                .expect("failed to write image");
         }
     }
 }
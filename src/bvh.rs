@@ -0,0 +1,270 @@
+use crate::{Aabb, HitRecord, Hittable, HittableList, Ray};
+use std::sync::Arc;
+
+// bounding volume hierarchy: a binary tree of bounding boxes, so that a ray
+// only tests the objects whose boxes it actually passes through. objects
+// without a bounding box (such as a Plane) can't go in the tree, so they are
+// kept aside and tested against every ray
+pub struct BvhNode {
+    tree: Option<BvhTree>,
+    unbounded: Vec<Arc<dyn Hittable>>,
+}
+
+impl BvhNode {
+    // the list must not be empty
+    pub fn new(list: HittableList) -> Self {
+        assert!(
+            !list.objects().is_empty(),
+            "BvhNode needs at least one object"
+        );
+        let mut bounded = vec![];
+        let mut unbounded = vec![];
+        for object in list.objects() {
+            match object.bounding_box() {
+                Some(bbox) => bounded.push((object.clone(), bbox)),
+                None => unbounded.push(object.clone()),
+            }
+        }
+        let tree = if bounded.is_empty() {
+            None
+        } else {
+            Some(BvhTree::build(bounded))
+        };
+        Self { tree, unbounded }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = self
+            .tree
+            .as_ref()
+            .and_then(|tree| tree.hit(r, t_min, t_max));
+        for object in &self.unbounded {
+            let t_max = closest.as_ref().map_or(t_max, |rec| rec.t);
+            if let Some(rec) = object.hit(r, t_min, t_max) {
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.tree.as_ref().map(|tree| tree.bbox)
+    }
+}
+
+// a node of the hierarchy over objects that all have bounding boxes
+struct BvhTree {
+    left: Arc<dyn Hittable>,
+    right: Option<Arc<dyn Hittable>>, // None for a leaf holding one object
+    bbox: Aabb,
+}
+
+impl BvhTree {
+    fn build(mut objects: Vec<(Arc<dyn Hittable>, Aabb)>) -> Self {
+        let bbox = objects
+            .iter()
+            .map(|&(_, bbox)| bbox)
+            .reduce(Aabb::surrounding_box)
+            .unwrap();
+        if objects.len() == 1 {
+            let (object, _) = objects.pop().unwrap();
+            return Self {
+                left: object,
+                right: None,
+                bbox,
+            };
+        }
+
+        let (axis, split) = Self::sah_split(&mut objects);
+        sort_by_centroid(&mut objects, axis);
+        let right_objects = objects.split_off(split);
+        Self {
+            left: Self::subtree(objects),
+            right: Some(Self::subtree(right_objects)),
+            bbox,
+        }
+    }
+
+    fn subtree(mut objects: Vec<(Arc<dyn Hittable>, Aabb)>) -> Arc<dyn Hittable> {
+        if objects.len() == 1 {
+            objects.pop().unwrap().0
+        } else {
+            Arc::new(Self::build(objects))
+        }
+    }
+
+    // surface area heuristic: the chance of a ray hitting a child is roughly
+    // proportional to its surface area, so pick the axis and split position
+    // minimising sum(child area * child object count)
+    fn sah_split(objects: &mut [(Arc<dyn Hittable>, Aabb)]) -> (usize, usize) {
+        let n = objects.len();
+        let mut best = (0, n / 2);
+        let mut best_cost = f64::INFINITY;
+        for axis in 0..3 {
+            sort_by_centroid(objects, axis);
+
+            // right_areas[i] is the area of the box around objects[i..]
+            let mut right_areas = vec![0.0; n];
+            let mut right_box = objects[n - 1].1;
+            for i in (1..n).rev() {
+                right_box = Aabb::surrounding_box(right_box, objects[i].1);
+                right_areas[i] = right_box.surface_area();
+            }
+
+            let mut left_box = objects[0].1;
+            for split in 1..n {
+                left_box = Aabb::surrounding_box(left_box, objects[split - 1].1);
+                let cost = left_box.surface_area() * split as f64
+                    + right_areas[split] * (n - split) as f64;
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = (axis, split);
+                }
+            }
+        }
+        best
+    }
+}
+
+fn sort_by_centroid(objects: &mut [(Arc<dyn Hittable>, Aabb)], axis: usize) {
+    objects.sort_by(|(_, a), (_, b)| {
+        a.centroid()[axis]
+            .partial_cmp(&b.centroid()[axis])
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+impl Hittable for BvhTree {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None;
+        }
+        let hit_left = self.left.hit(r, t_min, t_max);
+        // anything in the right subtree must be closer than the left hit
+        let t_max = hit_left.as_ref().map_or(t_max, |rec| rec.t);
+        let hit_right = self
+            .right
+            .as_ref()
+            .and_then(|right| right.hit(r, t_min, t_max));
+        hit_right.or(hit_left)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{Color, Lambertian, Pcg32, Plane, Point3, Sphere, Vec3};
+
+    fn random_spheres(rng: &mut Pcg32, count: usize) -> (HittableList, HittableList) {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::default();
+        let mut bvh_list = HittableList::default();
+        for _ in 0..count {
+            let sphere = Arc::new(Sphere::new(
+                Vec3::rand_in_range(rng, -10.0, 10.0),
+                rng.rand_in_range(0.1, 1.5),
+                material.clone(),
+            ));
+            list.add(sphere.clone());
+            bvh_list.add(sphere);
+        }
+        (list, bvh_list)
+    }
+
+    #[test]
+    fn bvh_agrees_with_list_on_random_rays() {
+        let mut rng = Pcg32::new(7);
+        let (list, bvh_list) = random_spheres(&mut rng, 200);
+        let bvh = BvhNode::new(bvh_list);
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let r = Ray::new(
+                Vec3::rand_in_range(&mut rng, -15.0, 15.0),
+                Vec3::rand_in_range(&mut rng, -1.0, 1.0),
+            );
+            let expected = list.hit(r, 0.001, f64::INFINITY);
+            let actual = bvh.hit(r, 0.001, f64::INFINITY);
+            assert_eq!(expected.is_some(), actual.is_some());
+            if let (Some(expected), Some(actual)) = (expected, actual) {
+                hits += 1;
+                assert_f64_eq(expected.t, actual.t);
+                assert_eq_vec3s(expected.normal, actual.normal);
+            }
+        }
+        // make sure the comparison wasn't vacuous
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn bvh_box_surrounds_all_objects() {
+        let (list, bvh_list) = random_spheres(&mut Pcg32::new(11), 50);
+        let bvh = BvhNode::new(bvh_list);
+        let expected = list.bounding_box().unwrap();
+        let actual = bvh.bounding_box().unwrap();
+        assert_eq_vec3s(expected.min(), actual.min());
+        assert_eq_vec3s(expected.max(), actual.max());
+    }
+
+    #[test]
+    fn single_object_bvh() {
+        let mut list = HittableList::default();
+        list.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -2.0),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let bvh = BvhNode::new(list);
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        assert_f64_eq(bvh.hit(r, 0.001, f64::INFINITY).unwrap().t, 1.5);
+    }
+
+    #[test]
+    fn unbounded_objects_are_kept_aside() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::default();
+        list.add(Arc::new(Plane::new(
+            Point3::zero(),
+            Vec3::new(0.0, 1.0, 0.0),
+            material.clone(),
+        )));
+        list.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 1.0, -2.0),
+            0.5,
+            material.clone(),
+        )));
+        let bvh = BvhNode::new(list);
+        assert!(bvh.bounding_box().is_none());
+        // the sphere in front of the plane, and the plane on its own
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_f64_eq(bvh.hit(r, 0.001, f64::INFINITY).unwrap().t, 1.5);
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, -1.0));
+        let rec = bvh.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(0.0, 0.0, -1.0));
+
+        // planes alone are fine too
+        let mut planes = HittableList::default();
+        planes.add(Arc::new(Plane::new(
+            Point3::zero(),
+            Vec3::new(0.0, 1.0, 0.0),
+            material,
+        )));
+        let bvh = BvhNode::new(planes);
+        assert!(bvh.hit(r, 0.001, f64::INFINITY).is_some());
+    }
+
+    #[test]
+    #[should_panic]
+    fn empty_bvh_panics() {
+        BvhNode::new(HittableList::default());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{degrees_to_radians, Point3, Ray, Vec3};
+use crate::{degrees_to_radians, sample_concentric_disk, Point3, Ray, Vec3};
 
 pub struct Camera {
     origin: Point3,
@@ -8,6 +8,9 @@ pub struct Camera {
     u: Vec3,
     v: Vec3,
     lens_radius: f64,
+    // shutter open and close times
+    time0: f64,
+    time1: f64,
 }
 
 impl Camera {
@@ -44,12 +47,25 @@ impl Camera {
             u,
             v,
             lens_radius,
+            time0: 0.0,
+            time1: 0.0,
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::rand_in_unit_disk();
-        let offset = self.u * rd.x() + self.v * rd.y();
+    // rays are spread uniformly over the time the shutter is open, blurring
+    // moving objects; by default it opens and closes at time 0
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.time0 = open;
+        self.time1 = close;
+        self
+    }
+
+    // (s, t) is the position on the viewport, lens_sample a uniform point in
+    // [0, 1)^2 mapped onto the lens and time_sample a uniform value in [0, 1)
+    // mapped onto the shutter interval
+    pub fn get_ray(&self, s: f64, t: f64, lens_sample: (f64, f64), time_sample: f64) -> Ray {
+        let (lens_x, lens_y) = sample_concentric_disk(lens_sample);
+        let offset = self.lens_radius * (self.u * lens_x + self.v * lens_y);
 
         Ray::new(
             self.origin + offset,
@@ -57,5 +73,41 @@ impl Camera {
                 + (self.horizontal * s)
                 + (self.vertical * t),
         )
+        .with_time(self.time0 + time_sample * (self.time1 - self.time0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    fn camera() -> Camera {
+        Camera::new(
+            Point3::zero(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
+            0.0,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn rays_go_through_the_viewport() {
+        let r = camera().get_ray(0.5, 0.5, (0.5, 0.5), 0.5);
+        assert_eq_vec3s(r.origin(), Point3::zero());
+        assert_eq_vec3s(Vec3::unit(r.direction()), Vec3::new(0.0, 0.0, -1.0));
+        let r = camera().get_ray(1.0, 1.0, (0.5, 0.5), 0.5);
+        assert_eq_vec3s(r.direction(), Vec3::new(2.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn shutter_spreads_ray_times() {
+        assert_eq!(camera().get_ray(0.5, 0.5, (0.5, 0.5), 0.7).time(), 0.0);
+        let camera = camera().with_shutter(1.0, 3.0);
+        assert_f64_eq(camera.get_ray(0.5, 0.5, (0.5, 0.5), 0.0).time(), 1.0);
+        assert_f64_eq(camera.get_ray(0.5, 0.5, (0.5, 0.5), 0.25).time(), 1.5);
     }
 }
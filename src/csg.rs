@@ -0,0 +1,267 @@
+use crate::{Aabb, HitRecord, Hittable, Point3, Ray};
+use std::sync::Arc;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // the left solid with the right one carved out of it
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// constructive solid geometry over two closed hittables, which may be Csg
+// nodes themselves. the crossings of both along a ray are merged, keeping
+// those where the ray enters or leaves the combined solid. hits keep the
+// material of the surface they come from, so e.g. a cut can show a different
+// material than the outside
+pub struct Csg {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    operation: CsgOperation,
+}
+
+impl Csg {
+    pub fn new(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>, operation: CsgOperation) -> Self {
+        Self {
+            left,
+            right,
+            operation,
+        }
+    }
+
+    pub fn union(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(left, right, CsgOperation::Union)
+    }
+
+    pub fn intersection(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(left, right, CsgOperation::Intersection)
+    }
+
+    pub fn difference(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(left, right, CsgOperation::Difference)
+    }
+
+    pub fn operation(&self) -> CsgOperation {
+        self.operation
+    }
+}
+
+// whether the ray starts inside a closed solid, given all its crossings
+// from there on: it does if the first one leaves the solid
+fn starts_inside(hits: &[HitRecord]) -> bool {
+    matches!(hits.first(), Some(rec) if !rec.is_front_face)
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.all_hits(r, t_min, t_max).into_iter().next()
+    }
+
+    fn all_hits(&self, r: Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        // the operands are searched to infinity, so that a ray starting
+        // inside one can tell from its first crossing
+        let left = self.left.all_hits(r, t_min, f64::INFINITY);
+        let right = self.right.all_hits(r, t_min, f64::INFINITY);
+        let mut in_left = starts_inside(&left);
+        let mut in_right = starts_inside(&right);
+        let mut inside = self.operation.contains(in_left, in_right);
+
+        let mut hits = Vec::new();
+        let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+        loop {
+            let from_left = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) => l.t <= r.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let mut rec = if from_left {
+                let rec = left.next().unwrap();
+                in_left = rec.is_front_face;
+                rec
+            } else {
+                let rec = right.next().unwrap();
+                in_right = rec.is_front_face;
+                rec
+            };
+            if rec.t > t_max {
+                break;
+            }
+            let now_inside = self.operation.contains(in_left, in_right);
+            if now_inside != inside {
+                // the normal already faces the ray, whichever way the
+                // surface is oriented in the result (a carved out surface
+                // is turned inside out), so only which side was hit changes
+                rec.is_front_face = now_inside;
+                hits.push(rec);
+                inside = now_inside;
+            }
+        }
+        hits
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let left = self.left.bounding_box();
+        let right = self.right.bounding_box();
+        match self.operation {
+            CsgOperation::Union => Some(Aabb::surrounding_box(left?, right?)),
+            CsgOperation::Intersection => match (left, right) {
+                (Some(a), Some(b)) => {
+                    let low = Point3::new(
+                        a.min().x().max(b.min().x()),
+                        a.min().y().max(b.min().y()),
+                        a.min().z().max(b.min().z()),
+                    );
+                    let high = Point3::new(
+                        a.max().x().min(b.max().x()),
+                        a.max().y().min(b.max().y()),
+                        a.max().z().min(b.max().z()),
+                    );
+                    Some(Aabb::new(low, high))
+                }
+                (bbox, None) | (None, bbox) => bbox,
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{Cylinder, Sphere, Transformed, Vec3};
+
+    fn sphere(x: f64, radius: f64) -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(
+            Point3::new(x, 0.0, 0.0),
+            radius,
+            grey_lambertian(),
+        ))
+    }
+
+    // the x coordinates of every crossing along the x axis, from far left,
+    // and whether each enters the solid
+    fn crossings(object: &dyn Hittable) -> Vec<(f64, bool)> {
+        let r = Ray::new(Point3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        object
+            .all_hits(r, 0.001, f64::INFINITY)
+            .iter()
+            .map(|rec| (rec.point.x(), rec.is_front_face))
+            .collect()
+    }
+
+    fn assert_crossings(object: &dyn Hittable, expected: &[(f64, bool)]) {
+        let actual = crossings(object);
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (&(x, entering), &(expected_x, expected_entering)) in actual.iter().zip(expected) {
+            assert_f64_eq(x, expected_x);
+            assert_eq!(entering, expected_entering);
+        }
+    }
+
+    #[test]
+    fn default_all_hits_walks_the_surface() {
+        assert_crossings(sphere(0.0, 1.0).as_ref(), &[(-1.0, true), (1.0, false)]);
+    }
+
+    #[test]
+    fn operations_on_overlapping_spheres() {
+        // spheres over [-2, 0] and [-1, 1] along x
+        let (a, b) = (sphere(-1.0, 1.0), sphere(0.0, 1.0));
+        assert_crossings(
+            &Csg::union(a.clone(), b.clone()),
+            &[(-2.0, true), (1.0, false)],
+        );
+        assert_crossings(
+            &Csg::intersection(a.clone(), b.clone()),
+            &[(-1.0, true), (0.0, false)],
+        );
+        assert_crossings(
+            &Csg::difference(a.clone(), b.clone()),
+            &[(-2.0, true), (-1.0, false)],
+        );
+        assert_crossings(&Csg::difference(b, a), &[(0.0, true), (1.0, false)]);
+    }
+
+    #[test]
+    fn hollow_ball() {
+        let shell = Csg::difference(sphere(0.0, 1.0), sphere(0.0, 0.9));
+        assert_crossings(
+            &shell,
+            &[(-1.0, true), (-0.9, false), (0.9, true), (1.0, false)],
+        );
+        // leaving the glass into the cavity: the normal faces the ray
+        let r = Ray::new(Point3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = shell.hit(r, 9.05, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(-0.9, 0.0, 0.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!(!rec.is_front_face);
+        // and t_max cuts off the far crossings
+        assert_eq!(shell.all_hits(r, 0.001, 9.05).len(), 1);
+    }
+
+    #[test]
+    fn rays_starting_inside() {
+        let shell = Csg::difference(sphere(0.0, 1.0), sphere(0.0, 0.9));
+        // from the middle of the cavity, out through the glass
+        let r = Ray::new(Point3::zero(), Vec3::new(1.0, 0.0, 0.0));
+        let hits = shell.all_hits(r, 0.001, f64::INFINITY);
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].is_front_face);
+        assert_f64_eq(hits[0].t, 0.9);
+        // from inside the glass itself
+        let r = Ray::new(Point3::new(0.95, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = shell.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert!(!rec.is_front_face);
+        assert_f64_eq(rec.t, 0.05);
+    }
+
+    #[test]
+    fn nested_and_transformed_operands() {
+        // a sphere with a hole drilled along x, by a cylinder turned onto
+        // the x axis
+        let drill = Transformed::new(Cylinder::new(0.5, 4.0, grey_lambertian()))
+            .translate(Vec3::new(0.0, -2.0, 0.0))
+            .rotate(Vec3::new(0.0, 0.0, 1.0), -90.0);
+        let drilled = Arc::new(Csg::difference(sphere(0.0, 1.0), Arc::new(drill)));
+        assert!(crossings(drilled.as_ref()).is_empty());
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = drilled.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_f64_eq(rec.point.z(), 1.0);
+        // the hole's wall, seen from inside it
+        let r = Ray::new(Point3::new(3.0, 0.0, 0.0), Vec3::new(-1.0, -0.2, 0.0));
+        let rec = drilled.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert!(rec.is_front_face);
+        assert_eq_vec3s(rec.point, Point3::new(0.5, -0.5, 0.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        let with_ball = Csg::union(drilled, sphere(0.0, 0.3));
+        assert_crossings(&with_ball, &[(-0.3, true), (0.3, false)]);
+    }
+
+    #[test]
+    fn bounding_boxes() {
+        let (a, b) = (sphere(-1.0, 1.0), sphere(0.0, 1.0));
+        let bbox = Csg::union(a.clone(), b.clone()).bounding_box().unwrap();
+        assert_eq_vec3s(bbox.min(), Point3::new(-2.0, -1.0, -1.0));
+        assert_eq_vec3s(bbox.max(), Point3::new(1.0, 1.0, 1.0));
+        let bbox = Csg::intersection(a.clone(), b.clone())
+            .bounding_box()
+            .unwrap();
+        assert_eq_vec3s(bbox.min(), Point3::new(-1.0, -1.0, -1.0));
+        assert_eq_vec3s(bbox.max(), Point3::new(0.0, 1.0, 1.0));
+        let bbox = Csg::difference(a, b).bounding_box().unwrap();
+        assert_eq_vec3s(bbox.max(), Point3::new(0.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,152 @@
+// piecewise-constant probability distribution over [0, 1), proportional to
+// a tabulated non-negative function
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Self {
+        assert!(!func.is_empty(), "distribution needs at least one value");
+        let n = func.len() as f64;
+        let mut cdf = vec![0.0; func.len() + 1];
+        for i in 0..func.len() {
+            cdf[i + 1] = cdf[i] + func[i].max(0.0) / n;
+        }
+        let integral = cdf[func.len()];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            // an all-zero function falls back to a uniform distribution
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f64 / n
+            };
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    // average value of the function over [0, 1)
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // maps a uniform u in [0, 1) to (x, pdf(x), index of x's segment)
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // last segment whose cdf starts at or below u
+        let offset = (self.cdf.partition_point(|&c| c <= u).max(1) - 1).min(self.count() - 1);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+        let x = (offset as f64 + du) / self.count() as f64;
+        (x.min(1.0 - f64::EPSILON), self.pdf_at(offset), offset)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let offset = ((x * self.count() as f64) as usize).min(self.count() - 1);
+        self.pdf_at(offset)
+    }
+
+    fn pdf_at(&self, offset: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[offset].max(0.0) / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+// piecewise-constant distribution over [0, 1)^2 given a row-major table
+// (rows along v), sampled by picking a row and then a column within it
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+        let conditional: Vec<Distribution1D> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    // maps uniform (u0, u1) to ((u, v), pdf(u, v))
+    pub fn sample(&self, u0: f64, u1: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u1);
+        let (u, pdf_u, _) = self.conditional[row].sample(u0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = ((v * self.conditional.len() as f64) as usize).min(self.conditional.len() - 1);
+        self.marginal.pdf(v) * self.conditional[row].pdf(u)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn samples_follow_function() {
+        let dist = Distribution1D::new(vec![1.0, 3.0]);
+        let (x, pdf, offset) = dist.sample(0.1);
+        assert_eq!(offset, 0);
+        assert_f64_eq(pdf, 0.5);
+        assert_f64_eq(x, 0.2);
+
+        let (x, pdf, offset) = dist.sample(0.625);
+        assert_eq!(offset, 1);
+        assert_f64_eq(pdf, 1.5);
+        assert_f64_eq(x, 0.75);
+    }
+
+    #[test]
+    fn zero_segments_are_never_sampled() {
+        let dist = Distribution1D::new(vec![0.0, 1.0, 0.0]);
+        for i in 0..100 {
+            let (x, _, offset) = dist.sample(i as f64 / 100.0);
+            assert_eq!(offset, 1);
+            assert!((1.0 / 3.0..2.0 / 3.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn all_zero_function_is_uniform() {
+        let dist = Distribution1D::new(vec![0.0, 0.0]);
+        let (x, pdf, _) = dist.sample(0.75);
+        assert_f64_eq(x, 0.75);
+        assert_f64_eq(pdf, 1.0);
+    }
+
+    #[test]
+    fn pdf_2d_matches_samples() {
+        let func = [1.0, 2.0, 3.0, 0.0, 5.0, 1.0];
+        let dist = Distribution2D::new(&func, 3, 2);
+        for &(u0, u1) in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.7), (0.3, 0.99)] {
+            let ((u, v), pdf) = dist.sample(u0, u1);
+            assert_f64_eq(pdf, dist.pdf(u, v));
+        }
+        // the pdf is the function normalised by its average (2)
+        assert_f64_eq(dist.pdf(0.5, 0.75), 2.5);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{
+    clamp, degrees_to_radians, luminance, sample_uniform_sphere, Color, Distribution2D, HdrError,
+    HdrImage, Vec3,
+};
+use std::{f64::consts::PI, path::Path};
+
+// light arriving from infinitely far away, seen by rays that escape the scene
+pub trait Environment: Send + Sync {
+    fn color(&self, direction: Vec3) -> Color;
+
+    // picks a unit direction to look for light in, given a uniform sample in
+    // [0, 1)^2, and returns it with its probability density per unit solid
+    // angle. integrators sampling lights directly use this to aim at the
+    // bright parts of the environment instead of finding them by chance
+    fn sample(&self, u: (f64, f64)) -> (Vec3, f64) {
+        (sample_uniform_sphere(u), 1.0 / (4.0 * PI))
+    }
+
+    // density with which sample returns the given direction
+    fn pdf(&self, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+// the same color in every direction, e.g. black for studio shots
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Environment for SolidColor {
+    fn color(&self, _direction: Vec3) -> Color {
+        self.color
+    }
+}
+
+// blends vertically from one color straight down to another straight up
+pub struct SkyGradient {
+    bottom: Color,
+    top: Color,
+}
+
+impl SkyGradient {
+    pub fn new(bottom: Color, top: Color) -> Self {
+        Self { bottom, top }
+    }
+}
+
+impl Default for SkyGradient {
+    // the book's white-to-blue sky
+    fn default() -> Self {
+        Self::new(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for SkyGradient {
+    fn color(&self, direction: Vec3) -> Color {
+        let unit_direction = Vec3::unit(direction);
+        let t = 0.5 * (unit_direction.y() + 1.0);
+        ((1.0 - t) * self.bottom) + (t * self.top)
+    }
+}
+
+// latitude-longitude (equirectangular) image wrapped around the scene: the
+// top row is straight up, and the center of the image faces -z (before
+// rotation). directions are importance sampled by pixel luminance
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>, // row by row from the top
+    rotation: f64,      // radians around the y axis
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "environment map can't be empty");
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "environment map needs width * height pixels"
+        );
+        // rows near the poles are squashed onto a smaller solid angle
+        let weights: Vec<f64> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, &pixel)| {
+                let theta = PI * ((i / width) as f64 + 0.5) / height as f64;
+                luminance(pixel) * theta.sin()
+            })
+            .collect();
+        let distribution = Distribution2D::new(&weights, width, height);
+        Self {
+            width,
+            height,
+            pixels,
+            rotation: 0.0,
+            intensity: 1.0,
+            distribution,
+        }
+    }
+
+    // loads a Radiance .hdr or .pfm latitude-longitude map
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HdrError> {
+        let image = HdrImage::load(path)?;
+        Ok(Self::new(image.width, image.height, image.pixels))
+    }
+
+    // spins the map counter-clockwise (seen from above) around the y axis
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees_to_radians(degrees);
+        self
+    }
+
+    // scales the brightness of the whole map
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // maps a unit direction to image coordinates in [0, 1]^2
+    pub fn direction_to_uv(direction: Vec3) -> (f64, f64) {
+        let phi = direction.x().atan2(-direction.z());
+        let theta = clamp(direction.y(), -1.0, 1.0).acos();
+        (0.5 + phi / (2.0 * PI), theta / PI)
+    }
+
+    // inverse of direction_to_uv
+    pub fn uv_to_direction(u: f64, v: f64) -> Vec3 {
+        let phi = (u - 0.5) * 2.0 * PI;
+        let theta = v * PI;
+        Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    fn lookup(&self, u: f64, v: f64) -> Color {
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixel(x, y)
+    }
+}
+
+// rotates v counter-clockwise (seen from above) around the y axis
+fn rotate_y(v: Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    Vec3::new(cos * v.x() + sin * v.z(), v.y(), -sin * v.x() + cos * v.z())
+}
+
+impl Environment for EnvironmentMap {
+    fn color(&self, direction: Vec3) -> Color {
+        let local = rotate_y(Vec3::unit(direction), -self.rotation);
+        let (u, v) = Self::direction_to_uv(local);
+        self.intensity * self.lookup(u, v)
+    }
+
+    fn sample(&self, u: (f64, f64)) -> (Vec3, f64) {
+        let ((map_u, map_v), map_pdf) = self.distribution.sample(u.0, u.1);
+        let direction = rotate_y(Self::uv_to_direction(map_u, map_v), self.rotation);
+        // change of variables from the unit square to the sphere
+        let sin_theta = (map_v * PI).sin();
+        if sin_theta <= 0.0 {
+            return (direction, 0.0);
+        }
+        (direction, map_pdf / (2.0 * PI * PI * sin_theta))
+    }
+
+    fn pdf(&self, direction: Vec3) -> f64 {
+        let local = rotate_y(Vec3::unit(direction), -self.rotation);
+        let (u, v) = Self::direction_to_uv(local);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn solid_color_is_constant() {
+        let env = SolidColor::new(Color::new(0.1, 0.2, 0.3));
+        assert_eq_vec3s(
+            env.color(Vec3::new(1.0, -2.0, 0.5)),
+            Color::new(0.1, 0.2, 0.3),
+        );
+    }
+
+    #[test]
+    fn gradient_blends_bottom_to_top() {
+        let env = SkyGradient::default();
+        assert_eq_vec3s(
+            env.color(Vec3::new(0.0, 3.0, 0.0)),
+            Color::new(0.5, 0.7, 1.0),
+        );
+        assert_eq_vec3s(
+            env.color(Vec3::new(0.0, -3.0, 0.0)),
+            Color::new(1.0, 1.0, 1.0),
+        );
+        assert_eq_vec3s(
+            env.color(Vec3::new(1.0, 0.0, 0.0)),
+            Color::new(0.75, 0.85, 1.0),
+        );
+    }
+
+    #[test]
+    fn uv_direction_round_trip() {
+        for &(u, v) in &[(0.5, 0.5), (0.1, 0.3), (0.9, 0.8), (0.25, 0.01)] {
+            let (u2, v2) = EnvironmentMap::direction_to_uv(EnvironmentMap::uv_to_direction(u, v));
+            assert_f64_eq(u, u2);
+            assert_f64_eq(v, v2);
+        }
+    }
+
+    #[test]
+    fn map_center_faces_negative_z() {
+        let (u, v) = EnvironmentMap::direction_to_uv(Vec3::new(0.0, 0.0, -1.0));
+        assert_f64_eq(u, 0.5);
+        assert_f64_eq(v, 0.5);
+    }
+
+    #[test]
+    fn map_lookup_uses_rows_from_top() {
+        let top = Color::new(1.0, 0.0, 0.0);
+        let bottom = Color::new(0.0, 0.0, 1.0);
+        let env = EnvironmentMap::new(2, 2, vec![top, top, bottom, bottom]);
+        assert_eq_vec3s(env.color(Vec3::new(0.3, 1.0, 0.0)), top);
+        assert_eq_vec3s(env.color(Vec3::new(0.3, -1.0, 0.0)), bottom);
+    }
+
+    // 8x4 black map with a single bright "sun" pixel
+    fn sun_map() -> EnvironmentMap {
+        let mut pixels = vec![Color::zero(); 32];
+        pixels[8 + 5] = Color::new(100.0, 90.0, 80.0);
+        EnvironmentMap::new(8, 4, pixels)
+    }
+
+    #[test]
+    fn rotation_and_intensity() {
+        let env = sun_map().with_rotation(90.0).with_intensity(0.5);
+        // the sun sits at u = 5.5 / 8 before rotation
+        let sun = EnvironmentMap::uv_to_direction(5.5 / 8.0, 1.5 / 4.0);
+        assert_eq_vec3s(env.color(sun), Color::zero());
+        let rotated_sun = rotate_y(sun, degrees_to_radians(90.0));
+        assert_eq_vec3s(env.color(rotated_sun), Color::new(50.0, 45.0, 40.0));
+    }
+
+    #[test]
+    fn sampling_finds_the_sun() {
+        let env = sun_map().with_rotation(30.0);
+        for &u in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.95)] {
+            let (direction, pdf) = env.sample(u);
+            assert!(env.color(direction).x() > 0.0);
+            assert_f64_eq(pdf, env.pdf(direction));
+        }
+    }
+
+    #[test]
+    fn map_pdf_integrates_to_one() {
+        let env = sun_map();
+        // riemann sum over a fine lat-long grid
+        let (n_u, n_v) = (256, 128);
+        let mut total = 0.0;
+        for j in 0..n_v {
+            for i in 0..n_u {
+                let (u, v) = ((i as f64 + 0.5) / n_u as f64, (j as f64 + 0.5) / n_v as f64);
+                let solid_angle = 2.0 * PI * PI * (v * PI).sin() / (n_u * n_v) as f64;
+                total += env.pdf(EnvironmentMap::uv_to_direction(u, v)) * solid_angle;
+            }
+        }
+        assert!((total - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn uniform_sampling_by_default() {
+        let (direction, pdf) = SolidColor::new(Color::zero()).sample((0.3, 0.6));
+        assert_f64_eq(direction.length(), 1.0);
+        assert_f64_eq(pdf, 1.0 / (4.0 * PI));
+    }
+}
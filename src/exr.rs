@@ -0,0 +1,313 @@
+use crate::Framebuffer;
+use std::io::{self, Write};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExrPixelType {
+    Half,
+    Float,
+}
+
+impl ExrPixelType {
+    // pixel type ids and sizes from the OpenEXR file layout
+    fn id(self) -> i32 {
+        match self {
+            ExrPixelType::Half => 1,
+            ExrPixelType::Float => 2,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ExrPixelType::Half => 2,
+            ExrPixelType::Float => 4,
+        }
+    }
+}
+
+struct ExrChannel {
+    name: String,
+    pixel_type: ExrPixelType,
+    values: Vec<f32>, // row by row from the top
+}
+
+// uncompressed single part scanline OpenEXR image with any number of named
+// channels, e.g. R, G, B plus render passes like "albedo.R" or "Z"
+pub struct ExrImage {
+    width: u32,
+    height: u32,
+    channels: Vec<ExrChannel>,
+}
+
+impl ExrImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        assert!(width > 0 && height > 0, "exr image can't be empty");
+        Self {
+            width,
+            height,
+            channels: vec![],
+        }
+    }
+
+    // values are given row by row from the top of the image
+    pub fn add_channel(&mut self, name: &str, pixel_type: ExrPixelType, values: Vec<f32>) {
+        assert!(!name.is_empty(), "exr channel needs a name");
+        assert_eq!(
+            values.len(),
+            (self.width * self.height) as usize,
+            "exr channel needs one value per pixel"
+        );
+        assert!(
+            self.channels.iter().all(|channel| channel.name != name),
+            "duplicate exr channel {}",
+            name
+        );
+        self.channels.push(ExrChannel {
+            name: name.to_string(),
+            pixel_type,
+            values,
+        });
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        // the format requires channels in alphabetical order, both in the
+        // header and within each scanline
+        let mut channels: Vec<&ExrChannel> = self.channels.iter().collect();
+        channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let header = self.header(&channels);
+        let line_size: usize = channels
+            .iter()
+            .map(|channel| channel.pixel_type.size() * self.width as usize)
+            .sum();
+        // each chunk is a scanline: y coordinate, data size and data
+        let chunk_size = 8 + line_size;
+
+        out.write_all(&header)?;
+        let first_chunk = header.len() + 8 * self.height as usize;
+        for y in 0..self.height as usize {
+            out.write_all(&((first_chunk + y * chunk_size) as u64).to_le_bytes())?;
+        }
+
+        let width = self.width as usize;
+        let mut line = Vec::with_capacity(line_size);
+        for y in 0..self.height as usize {
+            line.clear();
+            for channel in &channels {
+                for &value in &channel.values[y * width..(y + 1) * width] {
+                    match channel.pixel_type {
+                        ExrPixelType::Half => {
+                            line.extend_from_slice(&f32_to_half(value).to_le_bytes())
+                        }
+                        ExrPixelType::Float => line.extend_from_slice(&value.to_le_bytes()),
+                    }
+                }
+            }
+            out.write_all(&(y as i32).to_le_bytes())?;
+            out.write_all(&(line_size as i32).to_le_bytes())?;
+            out.write_all(&line)?;
+        }
+        out.flush()
+    }
+
+    fn header(&self, channels: &[&ExrChannel]) -> Vec<u8> {
+        let mut header = vec![];
+        // magic number, then version 2 with no flags (single part scanline)
+        header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        let mut chlist = vec![];
+        for channel in channels {
+            chlist.extend_from_slice(channel.name.as_bytes());
+            chlist.push(0);
+            chlist.extend_from_slice(&channel.pixel_type.id().to_le_bytes());
+            chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+            chlist.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+            chlist.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+        }
+        chlist.push(0);
+        write_attribute(&mut header, "channels", "chlist", &chlist);
+
+        write_attribute(&mut header, "compression", "compression", &[0]);
+        let mut window = vec![];
+        for &coordinate in &[0, 0, self.width as i32 - 1, self.height as i32 - 1] {
+            window.extend_from_slice(&coordinate.to_le_bytes());
+        }
+        write_attribute(&mut header, "dataWindow", "box2i", &window);
+        write_attribute(&mut header, "displayWindow", "box2i", &window);
+        write_attribute(&mut header, "lineOrder", "lineOrder", &[0]); // increasing y
+        write_attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        write_attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        header.push(0);
+        header
+    }
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, type_name: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(type_name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+// converts to an IEEE 754 half precision float, rounding to nearest even
+pub fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // infinity stays infinity, NaN stays NaN
+        let nan_bit = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan_bit;
+    }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exponent <= 0 {
+        // too small for a normal half: subnormal, or zero
+        if half_exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - half_exponent) as u32;
+        let round_bit = 1 << (shift - 1);
+        let mut half_mantissa = mantissa >> shift;
+        if mantissa & round_bit != 0 && mantissa & (3 * round_bit - 1) != 0 {
+            half_mantissa += 1;
+        }
+        return sign | half_mantissa as u16;
+    }
+
+    let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let round_bit = 0x1000;
+    // a carry out of the mantissa correctly bumps the exponent
+    let rounded = if mantissa & round_bit != 0 && mantissa & (3 * round_bit - 1) != 0 {
+        half + 1
+    } else {
+        half
+    };
+    sign | rounded as u16
+}
+
+impl Framebuffer {
+    // the averaged linear radiance as R, G and B channels
+    pub fn to_exr(&self, pixel_type: ExrPixelType) -> ExrImage {
+        let pixels = self.pixels();
+        let mut image = ExrImage::new(self.width(), self.height());
+        for (i, name) in ["R", "G", "B"].iter().enumerate() {
+            let values = pixels.iter().map(|pixel| pixel[i] as f32).collect();
+            image.add_channel(name, pixel_type, values);
+        }
+        image
+    }
+
+    pub fn write_exr<W: Write>(&self, out: W, pixel_type: ExrPixelType) -> io::Result<()> {
+        self.to_exr(pixel_type).write(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    #[test]
+    fn half_conversion() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(0.5), 0x3800);
+        assert_eq!(f32_to_half(-2.0), 0xc000);
+        assert_eq!(f32_to_half(65504.0), 0x7bff);
+        assert_eq!(f32_to_half(1e6), 0x7c00);
+        assert_eq!(f32_to_half(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_half(f32::NAN) & 0x7e00, 0x7e00);
+        // smallest subnormal half, and something that rounds down to zero
+        assert_eq!(f32_to_half(5.960_464_5e-8), 0x0001);
+        assert_eq!(f32_to_half(1e-9), 0x0000);
+        // 1 + 2^-11 is halfway between two halves: ties go to even
+        assert_eq!(f32_to_half(1.000_488_3), 0x3c00);
+        assert_eq!(f32_to_half(1.001_465), 0x3c02);
+    }
+
+    fn read_i32(data: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+    }
+
+    fn find(data: &[u8], needle: &[u8]) -> usize {
+        data.windows(needle.len())
+            .position(|window| window == needle)
+            .unwrap()
+    }
+
+    #[test]
+    fn writes_sorted_channels_and_offsets() {
+        let mut image = ExrImage::new(2, 3);
+        image.add_channel("Z", ExrPixelType::Float, vec![5.0; 6]);
+        image.add_channel("A", ExrPixelType::Half, vec![1.0; 6]);
+        let mut data = vec![];
+        image.write(&mut data).unwrap();
+
+        assert_eq!(&data[..4], &[0x76, 0x2f, 0x31, 0x01]);
+        assert!(find(&data, b"A\0") < find(&data, b"Z\0"));
+
+        let header_end = find(&data, b"screenWindowWidth\0float\0") + 24 + 8 + 1;
+        let line_size = 2 * 2 + 2 * 4;
+        for y in 0..3 {
+            let offset_at = header_end + 8 * y;
+            let offset = u64::from_le_bytes([
+                data[offset_at],
+                data[offset_at + 1],
+                data[offset_at + 2],
+                data[offset_at + 3],
+                data[offset_at + 4],
+                data[offset_at + 5],
+                data[offset_at + 6],
+                data[offset_at + 7],
+            ]) as usize;
+            assert_eq!(read_i32(&data, offset), y as i32);
+            assert_eq!(read_i32(&data, offset + 4), line_size as i32);
+            // the half channel A comes first, then Z as floats
+            assert_eq!(&data[offset + 8..offset + 10], &[0x00, 0x3c]);
+            assert_eq!(&data[offset + 12..offset + 16], &5f32.to_le_bytes());
+        }
+        assert_eq!(data.len(), header_end + 3 * 8 + 3 * (8 + line_size));
+    }
+
+    #[test]
+    fn framebuffer_exports_rgb() {
+        let mut framebuffer = Framebuffer::new(1, 1);
+        framebuffer.add_sample(0, 0, Color::new(2.0, 0.5, 8.0));
+        let mut data = vec![];
+        framebuffer
+            .write_exr(&mut data, ExrPixelType::Float)
+            .unwrap();
+        let tail = &data[data.len() - 12..];
+        // B, G, R in alphabetical order, unclamped
+        assert_eq!(&tail[..4], &8f32.to_le_bytes());
+        assert_eq!(&tail[4..8], &0.5f32.to_le_bytes());
+        assert_eq!(&tail[8..], &2f32.to_le_bytes());
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_duplicate_channels() {
+        let mut image = ExrImage::new(1, 1);
+        image.add_channel("R", ExrPixelType::Half, vec![0.0]);
+        image.add_channel("R", ExrPixelType::Half, vec![0.0]);
+    }
+}
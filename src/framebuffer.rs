@@ -0,0 +1,140 @@
+use crate::{Color, ToneMapping};
+use std::io::{self, Write};
+
+// accumulates linear radiance samples per pixel; rows are stored from the
+// top of the image down
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    sums: Vec<Color>,
+    sample_counts: Vec<u32>,
+}
+
+impl Framebuffer {
+    // panics if the image is too large to hold in memory at all
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .filter(|&len| {
+                matches!(len.checked_mul(std::mem::size_of::<Color>()),
+                    Some(bytes) if bytes <= isize::MAX as usize)
+            })
+            .expect("framebuffer too large");
+        Self {
+            width,
+            height,
+            sums: vec![Color::zero(); len],
+            sample_counts: vec![0; len],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub fn add_sample(&mut self, x: u32, y: u32, color: Color) {
+        self.add_samples(x, y, color, 1);
+    }
+
+    // adds the sum of several samples at once
+    pub fn add_samples(&mut self, x: u32, y: u32, sum: Color, count: u32) {
+        let i = self.index(x, y);
+        self.sums[i] += sum;
+        self.sample_counts[i] += count;
+    }
+
+    pub fn sample_count(&self, x: u32, y: u32) -> u32 {
+        self.sample_counts[self.index(x, y)]
+    }
+
+    // average of the pixel's samples, black if it has none
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let i = self.index(x, y);
+        match self.sample_counts[i] {
+            0 => Color::zero(),
+            count => self.sums[i] / count as f64,
+        }
+    }
+
+    // averaged pixels row by row from the top
+    pub fn pixels(&self) -> Vec<Color> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel(x, y))
+            .collect()
+    }
+
+    // ascii PPM (P3), tone mapped and sRGB encoded to 8 bits
+    pub fn write_ppm<W: Write>(&self, mut out: W, tone_mapping: &ToneMapping) -> io::Result<()> {
+        writeln!(out, "P3")?; // colors are in ascii
+        writeln!(out, "{} {}", self.width, self.height)?;
+        writeln!(out, "{}", 255)?;
+        for pixel in self.pixels() {
+            let [r, g, b] = tone_mapping.encode_u8(pixel);
+            writeln!(out, "{} {} {}", r, g, b)?;
+        }
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn pixel_averages_samples() {
+        let mut framebuffer = Framebuffer::new(2, 2);
+        framebuffer.add_sample(1, 0, Color::new(1.0, 0.0, 0.0));
+        framebuffer.add_sample(1, 0, Color::new(0.0, 0.5, 0.0));
+        framebuffer.add_samples(0, 1, Color::new(3.0, 3.0, 3.0), 6);
+        assert_eq!(framebuffer.sample_count(1, 0), 2);
+        assert_eq_vec3s(framebuffer.pixel(1, 0), Color::new(0.5, 0.25, 0.0));
+        assert_eq_vec3s(framebuffer.pixel(0, 1), Color::new(0.5, 0.5, 0.5));
+        assert_eq_vec3s(framebuffer.pixel(0, 0), Color::zero());
+    }
+
+    #[test]
+    fn pixels_are_rows_from_top() {
+        let mut framebuffer = Framebuffer::new(2, 1);
+        framebuffer.add_sample(1, 0, Color::new(1.0, 1.0, 1.0));
+        let pixels = framebuffer.pixels();
+        assert_eq_vec3s(pixels[0], Color::zero());
+        assert_eq_vec3s(pixels[1], Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn writes_ppm() {
+        let mut framebuffer = Framebuffer::new(2, 1);
+        framebuffer.add_sample(0, 0, Color::new(0.25, 1.0, 4.0));
+        let mut out = vec![];
+        framebuffer
+            .write_ppm(&mut out, &ToneMapping::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "P3\n2 1\n255\n137 255 255\n0 0 0\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "framebuffer too large")]
+    fn overflowing_dimensions_panic() {
+        Framebuffer::new(u32::MAX, u32::MAX);
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_bounds_pixel_panics() {
+        Framebuffer::new(2, 2).add_sample(2, 0, Color::zero());
+    }
+}
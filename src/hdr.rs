@@ -0,0 +1,382 @@
+use crate::Color;
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+#[derive(Debug)]
+pub enum HdrError {
+    Io(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for HdrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HdrError::Io(err) => write!(f, "{}", err),
+            HdrError::Format(message) => write!(f, "invalid image: {}", message),
+        }
+    }
+}
+
+impl Error for HdrError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HdrError::Io(err) => Some(err),
+            HdrError::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for HdrError {
+    fn from(err: io::Error) -> Self {
+        HdrError::Io(err)
+    }
+}
+
+fn format_error<T>(message: &str) -> Result<T, HdrError> {
+    Err(HdrError::Format(message.to_string()))
+}
+
+// linear floating point image, such as a latitude-longitude environment map
+pub struct HdrImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>, // row by row from the top
+}
+
+impl HdrImage {
+    // reads a Radiance .hdr or a .pfm file, depending on the extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HdrError> {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("pfm") => Self::read_pfm(reader),
+            _ => Self::read_rgbe(reader),
+        }
+    }
+
+    // Radiance RGBE: a text header, then scanlines of 4 byte shared-exponent
+    // pixels, either flat or run-length encoded per channel
+    pub fn read_rgbe<R: BufRead>(mut reader: R) -> Result<Self, HdrError> {
+        let magic = read_line(&mut reader)?;
+        if !magic.starts_with("#?") {
+            return format_error("missing Radiance header");
+        }
+        loop {
+            let line = read_line(&mut reader)?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return format_error(&format!("unsupported format {}", format));
+                }
+            }
+        }
+
+        // only the standard top-to-bottom, left-to-right orientation
+        let resolution = read_line(&mut reader)?;
+        let tokens: Vec<&str> = resolution.split_whitespace().collect();
+        let (height, width) = match tokens.as_slice() {
+            ["-Y", height, "+X", width] => (parse_dimension(height)?, parse_dimension(width)?),
+            _ => return format_error(&format!("unsupported resolution '{}'", resolution)),
+        };
+
+        check_size(width, height)?;
+        // grown a scanline at a time, so a truncated file fails before much
+        // is allocated for the pixels its header claims
+        let mut pixels = Vec::new();
+        let mut scanline = vec![[0u8; 4]; width];
+        for _ in 0..height {
+            read_rgbe_scanline(&mut reader, &mut scanline)?;
+            pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_color(rgbe)));
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    // portable float map: "PF" (rgb) or "Pf" (grey), dimensions, and a scale
+    // whose sign gives the endianness, followed by rows from the bottom
+    pub fn read_pfm<R: BufRead>(mut reader: R) -> Result<Self, HdrError> {
+        let channels = match read_token(&mut reader)?.as_str() {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return format_error("missing PFM header"),
+        };
+        let width = parse_dimension(&read_token(&mut reader)?)?;
+        let height = parse_dimension(&read_token(&mut reader)?)?;
+        let scale: f32 = read_token(&mut reader)?
+            .parse()
+            .or_else(|_| format_error("invalid PFM scale"))?;
+        let little_endian = scale < 0.0;
+
+        check_size(width, height)?;
+        // read a row at a time, as for RGBE
+        let mut rows = Vec::new();
+        let mut data = vec![0u8; width * channels * 4];
+        for _ in 0..height {
+            reader.read_exact(&mut data)?;
+            let floats: Vec<f64> = data
+                .chunks_exact(4)
+                .map(|bytes| {
+                    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                    let value = if little_endian {
+                        f32::from_le_bytes(bytes)
+                    } else {
+                        f32::from_be_bytes(bytes)
+                    };
+                    value as f64
+                })
+                .collect();
+            let row: Vec<Color> = floats
+                .chunks_exact(channels)
+                .map(|p| {
+                    if channels == 3 {
+                        Color::new(p[0], p[1], p[2])
+                    } else {
+                        Color::new(p[0], p[0], p[0])
+                    }
+                })
+                .collect();
+            rows.push(row);
+        }
+        let pixels = rows.into_iter().rev().flatten().collect();
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+// dimensions come straight from an untrusted header, so they are capped
+// before anything is allocated from them: a row is at most 65536 pixels wide
+// and an image at most 16384 by 16384 pixels, beyond any environment map
+// worth loading. the pixels themselves are only allocated as they are read
+const MAX_DIMENSION: usize = 1 << 16;
+const MAX_PIXELS: usize = 1 << 28;
+
+fn check_size(width: usize, height: usize) -> Result<(), HdrError> {
+    match width.checked_mul(height) {
+        Some(count) if width <= MAX_DIMENSION && height <= MAX_DIMENSION && count <= MAX_PIXELS => {
+            Ok(())
+        }
+        _ => format_error(&format!(
+            "image of {} by {} pixels is too large",
+            width, height
+        )),
+    }
+}
+
+fn parse_dimension(token: &str) -> Result<usize, HdrError> {
+    match token.parse() {
+        Ok(dimension) if dimension > 0 => Ok(dimension),
+        _ => format_error(&format!("invalid dimension '{}'", token)),
+    }
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<String, HdrError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return format_error("unexpected end of header");
+    }
+    Ok(line.trim_end().to_string())
+}
+
+// reads one whitespace-delimited token, consuming the single whitespace
+// byte after it (the last one before binary data)
+fn read_token<R: BufRead>(reader: &mut R) -> Result<String, HdrError> {
+    let mut token = String::new();
+    let mut byte = [0u8; 1];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0].is_ascii_whitespace() {
+            if token.is_empty() {
+                continue;
+            }
+            return Ok(token);
+        }
+        token.push(byte[0] as char);
+    }
+}
+
+fn read_rgbe_scanline<R: Read>(reader: &mut R, scanline: &mut [[u8; 4]]) -> Result<(), HdrError> {
+    let width = scanline.len();
+    let mut first = [0u8; 4];
+    reader.read_exact(&mut first)?;
+
+    // adaptive run-length encoding starts with 2, 2 and the width
+    let is_rle =
+        (8..0x8000).contains(&width) && first[0] == 2 && first[1] == 2 && first[2] & 0x80 == 0;
+    if !is_rle {
+        scanline[0] = first;
+        for pixel in scanline.iter_mut().skip(1) {
+            reader.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+    if ((first[2] as usize) << 8 | first[3] as usize) != width {
+        return format_error("scanline width mismatch");
+    }
+
+    // each channel is encoded separately as runs and literal dumps
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+            if count[0] > 128 {
+                let run = (count[0] - 128) as usize;
+                if run > width - x {
+                    return format_error("run-length overflows scanline");
+                }
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+                for pixel in &mut scanline[x..x + run] {
+                    pixel[channel] = value[0];
+                }
+                x += run;
+            } else {
+                let dump = count[0] as usize;
+                if dump == 0 || dump > width - x {
+                    return format_error("invalid literal run in scanline");
+                }
+                let mut values = vec![0u8; dump];
+                reader.read_exact(&mut values)?;
+                for (pixel, &value) in scanline[x..x + dump].iter_mut().zip(values.iter()) {
+                    pixel[channel] = value;
+                }
+                x += dump;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn rgbe_to_color([r, g, b, e]: [u8; 4]) -> Color {
+    if e == 0 {
+        return Color::zero();
+    }
+    let scale = 2_f64.powi(e as i32 - (128 + 8));
+    Color::new(r as f64, g as f64, b as f64) * scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    fn header(width: usize, height: usize) -> Vec<u8> {
+        format!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            height, width
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn reads_flat_rgbe() {
+        let mut data = header(2, 1);
+        data.extend_from_slice(&[128, 64, 32, 129, 0, 0, 0, 0]);
+        let image = HdrImage::read_rgbe(data.as_slice()).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq_vec3s(image.pixels[0], Color::new(1.0, 0.5, 0.25));
+        assert_eq_vec3s(image.pixels[1], Color::zero());
+    }
+
+    #[test]
+    fn reads_run_length_encoded_rgbe() {
+        let mut data = header(8, 1);
+        data.extend_from_slice(&[2, 2, 0, 8]);
+        // red: a run of 8; green: 8 literals; blue: two runs; exponent: one run
+        data.extend_from_slice(&[128 + 8, 128]);
+        data.extend_from_slice(&[8, 0, 16, 32, 48, 64, 80, 96, 112]);
+        data.extend_from_slice(&[128 + 4, 0, 128 + 4, 128]);
+        data.extend_from_slice(&[128 + 8, 136]);
+        let image = HdrImage::read_rgbe(data.as_slice()).unwrap();
+        assert_eq!(image.pixels.len(), 8);
+        assert_eq_vec3s(image.pixels[0], Color::new(128.0, 0.0, 0.0));
+        assert_eq_vec3s(image.pixels[7], Color::new(128.0, 112.0, 128.0));
+    }
+
+    #[test]
+    fn rejects_truncated_rgbe() {
+        let mut data = header(2, 2);
+        data.extend_from_slice(&[128, 64, 32, 129]);
+        assert!(HdrImage::read_rgbe(data.as_slice()).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_orientation() {
+        let data = b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0";
+        assert!(matches!(
+            HdrImage::read_rgbe(&data[..]),
+            Err(HdrError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_huge_dimensions_before_allocating() {
+        let huge = format!("{}", usize::MAX / 2);
+        let data = format!("PF\n{} {}\n-1.0\n", huge, huge);
+        assert!(matches!(
+            HdrImage::read_pfm(data.as_bytes()),
+            Err(HdrError::Format(_))
+        ));
+        let data = b"Pf 100000 100000 1.0\n";
+        assert!(matches!(
+            HdrImage::read_pfm(&data[..]),
+            Err(HdrError::Format(_))
+        ));
+        let data = b"Pf 1 1000000 1.0\n";
+        assert!(matches!(
+            HdrImage::read_pfm(&data[..]),
+            Err(HdrError::Format(_))
+        ));
+        let data = format!("#?RADIANCE\n\n-Y {} +X {}\n", huge, huge);
+        assert!(matches!(
+            HdrImage::read_rgbe(data.as_bytes()),
+            Err(HdrError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_images_of_the_largest_size_fail_without_allocating_it() {
+        let mut data = b"PF\n16384 16384\n-1.0\n".to_vec();
+        data.extend_from_slice(&[0; 64]);
+        assert!(matches!(
+            HdrImage::read_pfm(data.as_slice()),
+            Err(HdrError::Io(_))
+        ));
+        let mut data = header(16384, 16384);
+        data.extend_from_slice(&[128, 64, 32, 129]);
+        assert!(HdrImage::read_rgbe(data.as_slice()).is_err());
+    }
+
+    #[test]
+    fn reads_pfm_bottom_row_first() {
+        let mut data = b"PF\n1 2\n-1.0\n".to_vec();
+        for value in &[0.0f32, 0.5, 1.0, 2.0, 4.0, 8.0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let image = HdrImage::read_pfm(data.as_slice()).unwrap();
+        assert_eq!((image.width, image.height), (1, 2));
+        assert_eq_vec3s(image.pixels[0], Color::new(2.0, 4.0, 8.0));
+        assert_eq_vec3s(image.pixels[1], Color::new(0.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn reads_big_endian_grey_pfm() {
+        let mut data = b"Pf 1 1 1.0\n".to_vec();
+        data.extend_from_slice(&3.0f32.to_be_bytes());
+        let image = HdrImage::read_pfm(data.as_slice()).unwrap();
+        assert_eq_vec3s(image.pixels[0], Color::new(3.0, 3.0, 3.0));
+    }
+}
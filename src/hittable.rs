@@ -1,12 +1,18 @@
-use crate::{Material, Point3, Ray, Vec3};
-use std::rc::Rc;
+use crate::{Aabb, Material, Point3, Ray, Vec3};
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct HitRecord {
     pub point: Point3,
+    // the hit in the object's own space, before any Transformed or Instance
+    // placed it in the world, so that solid textures stick to the object
+    pub local_point: Point3,
     pub normal: Vec3,
-    pub mat_ptr: Rc<dyn Material>,
+    pub mat_ptr: Arc<dyn Material>,
     pub t: f64,
+    // surface coordinates of the hit, for looking up textures
+    pub u: f64,
+    pub v: f64,
     pub is_front_face: bool,
 }
 
@@ -24,6 +30,71 @@ impl HitRecord {
     }
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+
+    // box enclosing the whole object, or None if it is unbounded
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // whether pdf_value and random are implemented, so the object can be
+    // sampled as a light. objects that can't be are skipped by the light
+    // sampling integrators
+    fn can_sample(&self) -> bool {
+        false
+    }
+
+    // for objects sampled as lights: the density per unit solid angle with
+    // which random, called from origin at the given time, returns direction.
+    // zero if the direction misses the object
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3, _time: f64) -> f64 {
+        0.0
+    }
+
+    // a direction (not necessarily unit) from origin towards a point on the
+    // object where it is at the given time, given a uniform point u in
+    // [0, 1)^2
+    fn random(&self, _origin: Point3, _u: (f64, f64), _time: f64) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // every crossing of the surface along r within [t_min, t_max], nearest
+    // first. on a closed surface front faces enter the solid and back faces
+    // leave it, which is what Csg builds on. by default this walks hit along
+    // the ray, stepping just past each crossing
+    fn all_hits(&self, r: Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        let mut hits = Vec::new();
+        let mut t = t_min;
+        while let Some(rec) = self.hit(r, t, t_max) {
+            t = rec.t + 1e-7 * rec.t.abs().max(1.0);
+            hits.push(rec);
+        }
+        hits
+    }
+}
+
+// shared objects, such as geometry referenced by many instances
+impl<H: Hittable + ?Sized> Hittable for Arc<H> {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn can_sample(&self) -> bool {
+        (**self).can_sample()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        (**self).pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), time: f64) -> Vec3 {
+        (**self).random(origin, u, time)
+    }
+
+    fn all_hits(&self, r: Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        (**self).all_hits(r, t_min, t_max)
+    }
 }
@@ -1,22 +1,23 @@
-use crate::{HitRecord, Hittable, Ray};
-use std::rc::Rc;
+use crate::{Aabb, HitRecord, Hittable, Point3, Ray, Vec3};
+use std::sync::Arc;
 
+#[derive(Default)]
 pub struct HittableList {
-    objects: Vec<Rc<dyn Hittable>>,
+    objects: Vec<Arc<dyn Hittable>>,
 }
 
 impl HittableList {
-    pub fn default() -> Self {
-        Self { objects: vec![] }
-    }
-
     pub fn clear(&mut self) {
         self.objects.clear();
     }
 
-    pub fn add(&mut self, object: Rc<dyn Hittable>) {
+    pub fn add(&mut self, object: Arc<dyn Hittable>) {
         self.objects.push(object.clone());
     }
+
+    pub fn objects(&self) -> &[Arc<dyn Hittable>] {
+        &self.objects
+    }
 }
 
 impl Hittable for HittableList {
@@ -32,4 +33,40 @@ impl Hittable for HittableList {
         }
         hit_rec
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.objects.iter().map(|object| object.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |output_box, bbox| {
+            Some(Aabb::surrounding_box(output_box, bbox?))
+        })
+    }
+
+    // picks one of the objects uniformly, so the density is their average
+    // random picks any of the objects, so all of them must be sampleable
+    fn can_sample(&self) -> bool {
+        !self.objects.is_empty() && self.objects.iter().all(|object| object.can_sample())
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction, time))
+            .sum();
+        total / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), time: f64) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        // the first coordinate picks the object and is then reused
+        let scaled = u.0 * self.objects.len() as f64;
+        let index = (scaled as usize).min(self.objects.len() - 1);
+        self.objects[index].random(origin, (scaled - index as f64, u.1), time)
+    }
 }
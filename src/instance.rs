@@ -0,0 +1,145 @@
+use crate::{Aabb, HitRecord, Hittable, Material, Matrix4, Point3, Ray, Transformed, Vec3};
+use std::sync::Arc;
+
+// one placement of shared geometry. the geometry (typically a BvhNode built
+// once over a mesh: a bottom level acceleration structure) is only
+// referenced, so any number of instances cost one copy of it plus a
+// transform each. putting the instances themselves in a BvhNode gives the
+// top level of the hierarchy
+pub struct Instance {
+    geometry: Transformed<Arc<dyn Hittable>>,
+    material: Option<Arc<dyn Material>>,
+}
+
+impl Instance {
+    // object_to_world places the geometry in the world, and must be
+    // invertible
+    pub fn new(geometry: Arc<dyn Hittable>, object_to_world: Matrix4) -> Self {
+        Self {
+            geometry: Transformed::new(geometry).transform(object_to_world),
+            material: None,
+        }
+    }
+
+    // replaces the materials of the shared geometry for this instance only
+    pub fn with_material(mut self, material: Arc<dyn Material>) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn geometry(&self) -> &Arc<dyn Hittable> {
+        self.geometry.object()
+    }
+
+    pub fn object_to_world(&self) -> Matrix4 {
+        self.geometry.object_to_world()
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut rec = self.geometry.hit(r, t_min, t_max)?;
+        if let Some(material) = &self.material {
+            rec.mat_ptr = material.clone();
+        }
+        Some(rec)
+    }
+
+    fn all_hits(&self, r: Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        let mut hits = self.geometry.all_hits(r, t_min, t_max);
+        if let Some(material) = &self.material {
+            for rec in &mut hits {
+                rec.mat_ptr = material.clone();
+            }
+        }
+        hits
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.geometry.bounding_box()
+    }
+
+    fn can_sample(&self) -> bool {
+        self.geometry.can_sample()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        self.geometry.pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), time: f64) -> Vec3 {
+        self.geometry.random(origin, u, time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{BvhNode, Color, DiffuseLight, HittableList, Lambertian, Sphere};
+
+    // a small "tree": a trunk and a crown, built into its own BVH
+    fn tree() -> Arc<dyn Hittable> {
+        let material = Arc::new(Lambertian::new(Color::new(0.2, 0.5, 0.1)));
+        let mut parts = HittableList::default();
+        parts.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.5, 0.0),
+            0.2,
+            material.clone(),
+        )));
+        parts.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 1.5, 0.0),
+            0.8,
+            material,
+        )));
+        Arc::new(BvhNode::new(parts))
+    }
+
+    #[test]
+    fn instances_share_geometry() {
+        let tree = tree();
+        let mut forest = HittableList::default();
+        for i in 0..100 {
+            for j in 0..100 {
+                let position = Vec3::new(4.0 * i as f64, 0.0, -4.0 * j as f64);
+                forest.add(Arc::new(Instance::new(
+                    tree.clone(),
+                    Matrix4::translation(position),
+                )));
+            }
+        }
+        // one tree, referenced by 10,000 instances (and this function)
+        assert_eq!(Arc::strong_count(&tree), 10_001);
+        let forest = BvhNode::new(forest);
+
+        // looking down the z axis through the 4th row of crowns
+        let r = Ray::new(Point3::new(12.0, 1.5, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = forest.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(12.0, 1.5, 0.8));
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        let bbox = forest.bounding_box().unwrap();
+        assert_f64_eq(bbox.max().x(), 396.8);
+        assert_f64_eq(bbox.min().z(), -396.8);
+    }
+
+    #[test]
+    fn instances_can_override_materials() {
+        let emit = Color::new(1.0, 2.0, 3.0);
+        let instance = Instance::new(
+            tree(),
+            Matrix4::translation(Vec3::new(0.0, 0.0, -5.0))
+                * Matrix4::scaling(Vec3::new(2.0, 2.0, 2.0)),
+        )
+        .with_material(Arc::new(DiffuseLight::new(emit)));
+        let r = Ray::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = instance.hit(r, 0.001, f64::INFINITY).unwrap();
+        // the scaled crown has radius 1.6
+        assert_eq_vec3s(rec.point, Point3::new(0.0, 3.0, -3.4));
+        assert_eq_vec3s(rec.mat_ptr.emitted(&rec), emit);
+
+        let plain = Instance::new(tree(), Matrix4::identity());
+        let r = Ray::new(Point3::new(0.0, 1.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = plain.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.mat_ptr.emitted(&rec), Color::zero());
+    }
+}
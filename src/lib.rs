@@ -1,23 +1,73 @@
-use rand::Rng;
-
+mod aabb;
+mod bvh;
 mod camera;
 mod color;
+mod csg;
+mod distribution;
+mod environment;
+mod exr;
+mod framebuffer;
+mod hdr;
 mod hittable;
 mod hittable_list;
+mod instance;
 mod material;
+mod matrix;
+mod obj;
+mod output;
+mod path_depth;
+mod perlin;
+mod plane;
+mod quad;
+mod quadric;
 mod ray;
+mod render;
+mod rng;
+mod sampler;
+mod sampling;
 mod sphere;
+mod texture;
+mod tonemap;
+mod torus;
+mod transformed;
+mod triangle;
 mod vec3;
 pub type Vec3 = vec3::Vec3; // 3D vector
 pub type Point3 = vec3::Vec3; // 3D point
 pub type Color = vec3::Vec3; // RGB color
+pub use aabb::*;
+pub use bvh::*;
 pub use camera::*;
 pub use color::*;
+pub use csg::*;
+pub use distribution::*;
+pub use environment::*;
+pub use exr::*;
+pub use framebuffer::*;
+pub use hdr::*;
 pub use hittable::*;
 pub use hittable_list::*;
+pub use instance::*;
 pub use material::*;
+pub use matrix::*;
+pub use obj::*;
+pub use output::*;
+pub use path_depth::*;
+pub use perlin::*;
+pub use plane::*;
+pub use quad::*;
+pub use quadric::*;
 pub use ray::*;
+pub use render::*;
+pub use rng::*;
+pub use sampler::*;
+pub use sampling::*;
 pub use sphere::*;
+pub use texture::*;
+pub use tonemap::*;
+pub use torus::*;
+pub use transformed::*;
+pub use triangle::*;
 
 pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     if x < min {
@@ -33,16 +83,6 @@ pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * std::f64::consts::PI / 180.0
 }
 
-pub fn rand_f64() -> f64 {
-    rand_in_range(0.0, 1.0)
-}
-
-// returns a random real in [min, max)
-pub fn rand_in_range(min: f64, max: f64) -> f64 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..max)
-}
-
 #[cfg(test)]
 pub mod test_util {
     use super::*;
@@ -61,6 +101,22 @@ pub mod test_util {
         assert_f64_eq(v1.z(), v2.z());
     }
 
+    // a plain grey diffuse material, for tests that only care about shape
+    pub fn grey_lambertian() -> std::sync::Arc<dyn Material> {
+        std::sync::Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    // the centres of an n by n grid of cells over the unit square, for
+    // integrating over sample points by quadrature
+    pub fn grid(n: usize) -> impl Iterator<Item = (f64, f64)> {
+        (0..n * n).map(move |i| {
+            (
+                ((i % n) as f64 + 0.5) / n as f64,
+                ((i / n) as f64 + 0.5) / n as f64,
+            )
+        })
+    }
+
     prop_compose! {
         // strategy for normal non-NaN floats
         pub fn nf64()(float in -100.0..100.0) -> f64 {
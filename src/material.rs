@@ -1,7 +1,41 @@
-use crate::{clamp, rand_f64, Color, HitRecord, Ray, Vec3};
+use crate::{
+    clamp, luminance, sample_cosine_hemisphere, sample_unit_ball, Color, ConstantTexture,
+    HitRecord, Onb, Ray, Sampler, Texture, Vec3,
+};
+use std::{f64::consts::PI, sync::Arc};
 
-pub trait Material {
-    fn scatter(&self, r_in: Ray, rec: HitRecord) -> Option<Reflectance>;
+// directions are unit vectors in world space pointing away from the surface:
+// wo towards where the light ends up (back along the incoming ray), wi
+// towards where it comes from. rec.normal is always on wo's side
+pub trait Material: Send + Sync {
+    // the BSDF value f(wo, wi), not including the cosine term. zero for
+    // perfectly specular materials, which only ever scatter one way
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color;
+
+    // picks wi given a uniform value uc in [0, 1) (for choosing between
+    // lobes) and a uniform point u in [0, 1)^2, or None if the light is
+    // absorbed
+    fn sample(&self, rec: &HitRecord, wo: Vec3, uc: f64, u: (f64, f64)) -> Option<BsdfSample>;
+
+    // density per unit solid angle with which sample returns wi
+    fn pdf(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> f64;
+
+    // continues a random walk: samples the BSDF and weights it by
+    // f * cos / pdf, which for the book's materials is just their color
+    fn scatter(&self, r_in: Ray, rec: HitRecord, sampler: &mut dyn Sampler) -> Option<Reflectance> {
+        let wo = -Vec3::unit(r_in.direction());
+        let uc = sampler.get_1d();
+        let sample = self.sample(&rec, wo, uc, sampler.get_2d())?;
+        Some(Reflectance {
+            attenuation: sample.weight(rec.normal),
+            scattered_ray: Ray::new(rec.point, sample.wi).with_time(r_in.time()),
+        })
+    }
+
+    // light given off by the surface itself; most materials don't glow
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::zero()
+    }
 }
 
 pub struct Reflectance {
@@ -9,60 +43,171 @@ pub struct Reflectance {
     pub scattered_ray: Ray,
 }
 
+pub struct BsdfSample {
+    pub wi: Vec3,
+    pub f: Color,
+    pub pdf: f64,
+    // a mirror or glass direction, which no other strategy can ever pick and
+    // which eval and pdf don't know about
+    pub is_specular: bool,
+}
+
+impl BsdfSample {
+    // the sample's Monte Carlo weight f * |cos| / pdf
+    pub fn weight(&self, normal: Vec3) -> Color {
+        self.f * (Vec3::dot(self.wi, normal).abs() / self.pdf)
+    }
+}
+
+// a specular sample chosen with probability `pdf` whose weight is `weight`
+fn specular_sample(rec: &HitRecord, wi: Vec3, weight: Color, pdf: f64) -> Option<BsdfSample> {
+    let cos = Vec3::dot(wi, rec.normal).abs();
+    if cos == 0.0 || pdf == 0.0 {
+        return None;
+    }
+    Some(BsdfSample {
+        wi,
+        f: weight * (pdf / cos),
+        pdf,
+        is_specular: true,
+    })
+}
+
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Self::from_texture(Arc::new(ConstantTexture::new(albedo)))
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _: Ray, rec: HitRecord) -> Option<Reflectance> {
-        let mut scatter_direction = rec.normal + Vec3::rand_unit_vector();
-        // handle degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
+    fn eval(&self, rec: &HitRecord, _wo: Vec3, wi: Vec3) -> Color {
+        if Vec3::dot(wi, rec.normal) > 0.0 {
+            self.albedo.value(rec.u, rec.v, rec.local_point) / PI
+        } else {
+            Color::zero()
         }
-        Some(Reflectance {
-            scattered_ray: Ray::new(rec.point, scatter_direction),
-            attenuation: self.albedo,
+    }
+
+    // cosine weighted, so f * cos / pdf is exactly the albedo
+    fn sample(&self, rec: &HitRecord, wo: Vec3, _uc: f64, u: (f64, f64)) -> Option<BsdfSample> {
+        let wi = Onb::from_w(rec.normal).local(sample_cosine_hemisphere(u));
+        let pdf = self.pdf(rec, wo, wi);
+        if pdf == 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(rec, wo, wi),
+            pdf,
+            is_specular: false,
         })
     }
+
+    fn pdf(&self, rec: &HitRecord, _wo: Vec3, wi: Vec3) -> f64 {
+        Vec3::dot(wi, rec.normal).max(0.0) / PI
+    }
 }
 
+// the book's fuzzy mirror: the mirror direction is perturbed by a random
+// point in a ball of radius fuzz, and fuzz 0 is a perfect mirror
 pub struct Metal {
-    albedo: Color,
-    fuzz: f64,
+    albedo: Arc<dyn Texture>,
+    fuzz: Arc<dyn Texture>,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
-        Self {
-            albedo,
-            fuzz: clamp(fuzz, 0.0, 1.0),
+        let fuzz = clamp(fuzz, 0.0, 1.0);
+        Self::from_textures(
+            Arc::new(ConstantTexture::new(albedo)),
+            Arc::new(ConstantTexture::new(Color::new(fuzz, fuzz, fuzz))),
+        )
+    }
+
+    // the fuzz (roughness) is the luminance of its texture, so greyscale
+    // maps (best loaded with ImageTexture::load_linear) work as expected
+    pub fn from_textures(albedo: Arc<dyn Texture>, fuzz: Arc<dyn Texture>) -> Self {
+        Self { albedo, fuzz }
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, rec.local_point)
+    }
+
+    fn fuzz(&self, rec: &HitRecord) -> f64 {
+        clamp(
+            luminance(self.fuzz.value(rec.u, rec.v, rec.local_point)),
+            0.0,
+            1.0,
+        )
+    }
+
+    // density of the direction through a uniform point in the ball of radius
+    // fuzz around the mirror direction r: the volume of the ball along wi's
+    // infinitesimal cone, over the volume of the ball
+    fn lobe_pdf(fuzz: f64, r: Vec3, wi: Vec3) -> f64 {
+        let cos = Vec3::dot(wi, r);
+        let discriminant = cos * cos - (1.0 - fuzz * fuzz);
+        if cos <= 0.0 || discriminant < 0.0 {
+            return 0.0;
         }
+        let near = (cos - discriminant.sqrt()).max(0.0);
+        let far = cos + discriminant.sqrt();
+        (far.powi(3) - near.powi(3)) / (4.0 * PI * fuzz.powi(3))
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: Ray, rec: HitRecord) -> Option<Reflectance> {
-        let reflected = Vec3::reflect(Vec3::unit(r_in.direction()), rec.normal);
-        let scattered_ray = Ray::new(
-            rec.point,
-            reflected + self.fuzz * Vec3::rand_in_unit_sphere(),
-        );
-        let attenuation = self.albedo;
-        if Vec3::dot(reflected, rec.normal) > 0.0 {
-            Some(Reflectance {
-                scattered_ray,
-                attenuation,
-            })
-        } else {
-            None
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        let cos = Vec3::dot(wi, rec.normal);
+        if self.fuzz(rec) == 0.0 || cos <= 0.0 {
+            return Color::zero();
         }
+        // chosen so that f * cos / pdf is the albedo, like the book
+        self.albedo(rec) * (self.pdf(rec, wo, wi) / cos)
+    }
+
+    fn sample(&self, rec: &HitRecord, wo: Vec3, uc: f64, u: (f64, f64)) -> Option<BsdfSample> {
+        let reflected = Vec3::reflect(-wo, rec.normal);
+        let fuzz = self.fuzz(rec);
+        if fuzz == 0.0 {
+            return specular_sample(rec, reflected, self.albedo(rec), 1.0);
+        }
+        let direction = reflected + fuzz * sample_unit_ball(u, uc);
+        if direction.near_zero() {
+            return None;
+        }
+        let wi = Vec3::unit(direction);
+        // absorbed when fuzz pushes the ray below the surface
+        if Vec3::dot(wi, rec.normal) <= 0.0 {
+            return None;
+        }
+        let pdf = self.pdf(rec, wo, wi);
+        if pdf == 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(rec, wo, wi),
+            pdf,
+            is_specular: false,
+        })
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> f64 {
+        let fuzz = self.fuzz(rec);
+        if fuzz == 0.0 {
+            return 0.0;
+        }
+        Metal::lobe_pdf(fuzz, Vec3::reflect(-wo, rec.normal), wi)
     }
 }
 
@@ -85,26 +230,192 @@ impl Dieletric {
 }
 
 impl Material for Dieletric {
-    fn scatter(&self, r_in: Ray, rec: HitRecord) -> Option<Reflectance> {
+    fn eval(&self, _rec: &HitRecord, _wo: Vec3, _wi: Vec3) -> Color {
+        Color::zero()
+    }
+
+    // reflects with probability given by the Fresnel reflectance, else
+    // refracts, so that either way the weight is 1
+    fn sample(&self, rec: &HitRecord, wo: Vec3, uc: f64, _u: (f64, f64)) -> Option<BsdfSample> {
         let refraction_ratio = if rec.is_front_face {
             1.0 / self.index_of_refraction
         } else {
             self.index_of_refraction
         };
-        let unit_direction = Vec3::unit(r_in.direction());
+        let unit_direction = -wo;
 
-        let cos_theta = Vec3::dot(-unit_direction, rec.normal).min(1.0);
+        let cos_theta = Vec3::dot(wo, rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction =
-            if cannot_refract || Dieletric::reflectance(cos_theta, refraction_ratio) > rand_f64() {
-                Vec3::reflect(unit_direction, rec.normal)
-            } else {
-                Vec3::refract(unit_direction, rec.normal, refraction_ratio)
-            };
-        Some(Reflectance {
-            scattered_ray: Ray::new(rec.point, direction),
-            attenuation: Color::new(1.0, 1.0, 1.0),
-        })
+        let reflectance = if cannot_refract {
+            1.0
+        } else {
+            Dieletric::reflectance(cos_theta, refraction_ratio)
+        };
+        let white = Color::new(1.0, 1.0, 1.0);
+        if uc < reflectance {
+            let wi = Vec3::reflect(unit_direction, rec.normal);
+            specular_sample(rec, wi, white, reflectance)
+        } else {
+            let wi = Vec3::unit(Vec3::refract(unit_direction, rec.normal, refraction_ratio));
+            specular_sample(rec, wi, white, 1.0 - reflectance)
+        }
+    }
+
+    fn pdf(&self, _rec: &HitRecord, _wo: Vec3, _wi: Vec3) -> f64 {
+        0.0
+    }
+}
+
+// light source: absorbs everything hitting it and emits a constant color,
+// which may be brighter than 1 to act as a strong light
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn eval(&self, _rec: &HitRecord, _wo: Vec3, _wi: Vec3) -> Color {
+        Color::zero()
+    }
+
+    fn sample(&self, _rec: &HitRecord, _wo: Vec3, _uc: f64, _u: (f64, f64)) -> Option<BsdfSample> {
+        None
+    }
+
+    fn pdf(&self, _rec: &HitRecord, _wo: Vec3, _wi: Vec3) -> f64 {
+        0.0
+    }
+
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        self.emit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{sample_uniform_sphere, ImageTexture, Point3};
+    use std::sync::Arc;
+
+    fn record() -> HitRecord {
+        HitRecord {
+            point: Point3::zero(),
+            local_point: Point3::zero(),
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            mat_ptr: Arc::new(Lambertian::new(Color::zero())),
+            t: 1.0,
+            u: 0.0,
+            v: 0.0,
+            is_front_face: true,
+        }
+    }
+
+    // integral of the pdf over the sphere of directions, by quadrature
+    fn integrate_pdf(material: &dyn Material, rec: &HitRecord, wo: Vec3) -> f64 {
+        let n = 400;
+        let total: f64 = grid(n)
+            .map(|u| material.pdf(rec, wo, sample_uniform_sphere(u)))
+            .sum();
+        total * 4.0 * PI / (n * n) as f64
+    }
+
+    #[test]
+    fn lambertian_samples_are_cosine_weighted() {
+        let albedo = Color::new(0.5, 0.25, 1.0);
+        let material = Lambertian::new(albedo);
+        let rec = record();
+        let wo = Vec3::unit(Vec3::new(1.0, 0.0, 1.0));
+        for u in grid(8) {
+            let sample = material.sample(&rec, wo, 0.5, u).unwrap();
+            assert!(!sample.is_specular);
+            assert_f64_eq(sample.pdf, Vec3::dot(sample.wi, rec.normal) / PI);
+            assert_f64_eq(sample.pdf, material.pdf(&rec, wo, sample.wi));
+            assert_eq_vec3s(sample.f, material.eval(&rec, wo, sample.wi));
+            assert_eq_vec3s(sample.weight(rec.normal), albedo);
+        }
+        assert!((integrate_pdf(&material, &rec, wo) - 1.0).abs() < 1e-3);
+        assert_eq_vec3s(
+            material.eval(&rec, wo, Vec3::new(0.0, 0.0, -1.0)),
+            Color::zero(),
+        );
+    }
+
+    #[test]
+    fn fuzzy_metal_pdf_matches_samples() {
+        let albedo = Color::new(0.8, 0.6, 0.2);
+        let material = Metal::new(albedo, 0.4);
+        let rec = record();
+        let wo = Vec3::unit(Vec3::new(0.3, 0.0, 1.0));
+        // the ball around the mirror direction stays above the surface here
+        assert!((integrate_pdf(&material, &rec, wo) - 1.0).abs() < 1e-2);
+        for u in grid(8) {
+            let sample = material.sample(&rec, wo, 0.7, u).unwrap();
+            assert_f64_eq(sample.pdf, material.pdf(&rec, wo, sample.wi));
+            assert_eq_vec3s(sample.weight(rec.normal), albedo);
+        }
+    }
+
+    #[test]
+    fn textures_are_looked_up_at_the_hit() {
+        let texture = Arc::new(ImageTexture::new(
+            2,
+            1,
+            vec![Color::new(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0)],
+        ));
+        let material = Lambertian::from_texture(texture.clone());
+        let mut rec = record();
+        let wo = Vec3::new(0.0, 0.0, 1.0);
+        rec.u = 0.25;
+        assert_eq_vec3s(material.eval(&rec, wo, wo) * PI, Color::new(1.0, 0.0, 0.0));
+        rec.u = 0.75;
+        assert_eq_vec3s(material.eval(&rec, wo, wo) * PI, Color::new(0.0, 0.0, 1.0));
+
+        // the red half of the roughness map is a fuzzy metal, the blue half
+        // (with a luminance of 0.0722) nearly a mirror
+        let metal = Metal::from_textures(texture.clone(), texture);
+        rec.u = 0.25;
+        assert!(metal.pdf(&rec, wo, Vec3::unit(Vec3::new(0.1, 0.0, 1.0))) > 0.0);
+        rec.u = 0.75;
+        assert_eq!(
+            metal.pdf(&rec, wo, Vec3::unit(Vec3::new(0.1, 0.0, 1.0))),
+            0.0
+        );
+    }
+
+    #[test]
+    fn perfect_mirror_is_specular() {
+        let material = Metal::new(Color::new(0.9, 0.9, 0.9), 0.0);
+        let rec = record();
+        let wo = Vec3::unit(Vec3::new(1.0, 0.0, 1.0));
+        let sample = material.sample(&rec, wo, 0.5, (0.5, 0.5)).unwrap();
+        assert!(sample.is_specular);
+        assert_eq_vec3s(sample.wi, Vec3::unit(Vec3::new(-1.0, 0.0, 1.0)));
+        assert_eq_vec3s(sample.weight(rec.normal), Color::new(0.9, 0.9, 0.9));
+        assert_eq!(material.pdf(&rec, wo, sample.wi), 0.0);
+    }
+
+    #[test]
+    fn glass_reflects_with_fresnel_probability() {
+        let material = Dieletric::new(1.5);
+        let rec = record();
+        let wo = Vec3::new(0.0, 0.0, 1.0);
+        // 4% reflection at normal incidence
+        let reflected = material.sample(&rec, wo, 0.01, (0.0, 0.0)).unwrap();
+        assert_eq_vec3s(reflected.wi, wo);
+        assert_f64_eq(reflected.pdf, 0.04);
+        let refracted = material.sample(&rec, wo, 0.5, (0.0, 0.0)).unwrap();
+        assert_eq_vec3s(refracted.wi, -wo);
+        assert_f64_eq(refracted.pdf, 0.96);
+        for sample in &[reflected, refracted] {
+            assert!(sample.is_specular);
+            assert_eq_vec3s(sample.weight(rec.normal), Color::new(1.0, 1.0, 1.0));
+        }
     }
 }
@@ -0,0 +1,255 @@
+use crate::{degrees_to_radians, Point3, Vec3};
+use std::ops::Mul;
+
+// 4x4 matrix for affine transforms of points (w = 1) and vectors (w = 0),
+// stored row by row
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Matrix4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        Self::scaling(Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // counterclockwise rotation by `degrees` around `axis` (looking down the
+    // axis towards the origin), by Rodrigues' formula
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let a = Vec3::unit(axis);
+        let theta = degrees_to_radians(degrees);
+        let (sin, cos) = theta.sin_cos();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        Self::new([
+            [
+                cos + x * x * (1.0 - cos),
+                x * y * (1.0 - cos) - z * sin,
+                x * z * (1.0 - cos) + y * sin,
+                0.0,
+            ],
+            [
+                y * x * (1.0 - cos) + z * sin,
+                cos + y * y * (1.0 - cos),
+                y * z * (1.0 - cos) - x * sin,
+                0.0,
+            ],
+            [
+                z * x * (1.0 - cos) - y * sin,
+                z * y * (1.0 - cos) + x * sin,
+                cos + z * z * (1.0 - cos),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.m[row][column]
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Self::new(m)
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, or None if the matrix
+    // is singular
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::identity().m;
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+                .unwrap();
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inv.swap(column, pivot);
+            let scale = 1.0 / a[column][column];
+            for j in 0..4 {
+                a[column][j] *= scale;
+                inv[column][j] *= scale;
+            }
+            for i in 0..4 {
+                if i == column {
+                    continue;
+                }
+                let factor = a[i][column];
+                for j in 0..4 {
+                    a[i][j] -= factor * a[column][j];
+                    inv[i][j] -= factor * inv[column][j];
+                }
+            }
+        }
+        Some(Self::new(inv))
+    }
+
+    // determinant of the upper left 3x3 (linear) part, by which the
+    // transform scales volumes
+    pub fn linear_determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        if w == 1.0 {
+            Point3::new(x, y, z)
+        } else {
+            Point3::new(x, y, z) / w
+        }
+    }
+
+    // ignores the translation
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Self::new(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use proptest::prelude::proptest;
+
+    fn assert_eq_matrices(a: Matrix4, b: Matrix4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a.get(i, j) - b.get(i, j)).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn elementary_transforms() {
+        let p = Point3::new(1.0, 2.0, 3.0);
+        let offset = Vec3::new(1.0, -1.0, 0.5);
+        assert_eq_vec3s(
+            Matrix4::translation(offset).transform_point(p),
+            Point3::new(2.0, 1.0, 3.5),
+        );
+        // vectors don't move
+        assert_eq_vec3s(Matrix4::translation(offset).transform_vector(p), p);
+        assert_eq_vec3s(
+            Matrix4::scaling(Vec3::new(2.0, 3.0, -1.0)).transform_point(p),
+            Point3::new(2.0, 6.0, -3.0),
+        );
+        assert_eq_vec3s(
+            Matrix4::rotation(Vec3::new(0.0, 0.0, 1.0), 90.0).transform_vector(p),
+            Vec3::new(-2.0, 1.0, 3.0),
+        );
+        assert_eq_vec3s(
+            Matrix4::rotation(Vec3::new(0.0, 2.0, 0.0), 90.0).transform_vector(p),
+            Vec3::new(3.0, 2.0, -1.0),
+        );
+    }
+
+    #[test]
+    fn linear_determinant_scales_volumes() {
+        let m = Matrix4::translation(Vec3::new(5.0, 6.0, 7.0))
+            * Matrix4::rotation(Vec3::new(1.0, 1.0, 0.0), 33.0)
+            * Matrix4::scaling(Vec3::new(2.0, -3.0, 0.5));
+        assert_f64_eq(m.linear_determinant(), -3.0);
+        assert_f64_eq(m.inverse().unwrap().linear_determinant(), -1.0 / 3.0);
+    }
+
+    #[test]
+    fn products_apply_right_to_left() {
+        let p = Point3::new(1.0, 0.0, 0.0);
+        let m = Matrix4::translation(Vec3::new(0.0, 0.0, 5.0))
+            * Matrix4::rotation(Vec3::new(0.0, 0.0, 1.0), 90.0);
+        assert_eq_vec3s(m.transform_point(p), Point3::new(0.0, 1.0, 5.0));
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Matrix4::scaling(Vec3::new(1.0, 0.0, 1.0))
+            .inverse()
+            .is_none());
+    }
+
+    proptest! {
+        #[test]
+        fn inverse_undoes_transform(
+            axis in arb_vec3(),
+            degrees in nf64(),
+            offset in arb_vec3(),
+            p in arb_vec3(),
+        ) {
+            let axis = axis + Vec3::new(0.0, 0.0, 200.0);
+            let m = Matrix4::translation(offset)
+                * Matrix4::rotation(axis, degrees)
+                * Matrix4::scaling(Vec3::new(2.0, 0.5, 3.0));
+            let inverse = m.inverse().unwrap();
+            assert_eq_matrices(m * inverse, Matrix4::identity());
+            let back = inverse.transform_point(m.transform_point(p));
+            assert!((back - p).length() < 1e-9);
+        }
+
+        #[test]
+        fn rotation_inverse_is_transpose(axis in arb_vec3(), degrees in nf64()) {
+            let axis = axis + Vec3::new(0.0, 0.0, 200.0);
+            let m = Matrix4::rotation(axis, degrees);
+            assert_eq_matrices(m.inverse().unwrap(), m.transpose());
+        }
+    }
+}
@@ -0,0 +1,664 @@
+use crate::{
+    Color, Dieletric, DiffuseLight, HittableList, Lambertian, Material, Metal, Point3,
+    TriangleMesh, Vec3,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+// the faces of one group sharing one material
+pub struct ObjMesh {
+    pub group: String,
+    pub material_name: Option<String>,
+    pub mesh: Arc<TriangleMesh>,
+}
+
+// geometry loaded from a Wavefront .obj file
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+}
+
+impl ObjModel {
+    // faces without a usemtl statement get default_material. material
+    // libraries are looked up relative to the .obj file
+    pub fn load(
+        path: impl AsRef<Path>,
+        default_material: Arc<dyn Material>,
+    ) -> Result<Self, ObjError> {
+        let path = path.as_ref();
+        let file = open(path)?;
+        Self::parse(BufReader::new(file), path, default_material)
+    }
+
+    // like load, but reads the .obj statements from reader; path is only
+    // used for error messages and to locate material libraries
+    pub fn parse<R: BufRead>(
+        reader: R,
+        path: &Path,
+        default_material: Arc<dyn Material>,
+    ) -> Result<Self, ObjError> {
+        let mut positions = vec![];
+        let mut normals = vec![];
+        let mut uvs = vec![];
+        let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+        let mut builders: Vec<MeshBuilder> = vec![];
+        let mut current_group = String::from("default");
+        let mut current_material: Option<String> = None;
+
+        for (index, line) in reader.lines().enumerate() {
+            let ctx = LineContext {
+                path,
+                line: index + 1,
+            };
+            let line = line.map_err(|source| ObjError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            let mut tokens = line.split_whitespace();
+            let keyword = match tokens.next() {
+                Some(keyword) if !keyword.starts_with('#') => keyword,
+                _ => continue,
+            };
+            let args: Vec<&str> = tokens.collect();
+
+            match keyword {
+                "v" => positions.push(ctx.parse_vec3(&args)?),
+                "vn" => normals.push(ctx.parse_vec3(&args)?),
+                "vt" => {
+                    let u = ctx.parse_f64(args.first().copied())?;
+                    let v = match args.get(1) {
+                        Some(v) => ctx.parse_f64(Some(v))?,
+                        None => 0.0,
+                    };
+                    uvs.push((u, v));
+                }
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(ctx.error("a face needs at least three vertices"));
+                    }
+                    let face = args
+                        .iter()
+                        .map(|arg| {
+                            ctx.parse_face_vertex(arg, positions.len(), uvs.len(), normals.len())
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    let builder = match builders.iter().position(|builder| {
+                        builder.group == current_group && builder.material == current_material
+                    }) {
+                        Some(i) => &mut builders[i],
+                        None => {
+                            builders.push(MeshBuilder::new(&current_group, &current_material));
+                            builders.last_mut().unwrap()
+                        }
+                    };
+                    builder.add_polygon(&face, &positions, &uvs, &normals);
+                }
+                "g" | "o" => {
+                    current_group = if args.is_empty() {
+                        String::from("default")
+                    } else {
+                        args.join(" ")
+                    }
+                }
+                "usemtl" => {
+                    let name = args.join(" ");
+                    if !materials.contains_key(&name) {
+                        return Err(ctx.error(&format!("unknown material '{}'", name)));
+                    }
+                    current_material = Some(name);
+                }
+                "mtllib" => {
+                    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                    for file in args {
+                        // a library that can't be read is reported at the
+                        // mtllib line that names it
+                        let library = load_mtl(dir.join(file)).map_err(|err| match err {
+                            ObjError::Io { source, .. } => ctx.error(&format!(
+                                "cannot read material library '{}': {}",
+                                file, source
+                            )),
+                            err => err,
+                        })?;
+                        materials.extend(library);
+                    }
+                }
+                // smoothing groups, lines, points, curves... aren't supported
+                _ => {}
+            }
+        }
+
+        let meshes = builders
+            .into_iter()
+            .map(|builder| {
+                let mat_ptr = match &builder.material {
+                    Some(name) => materials[name].clone(),
+                    None => default_material.clone(),
+                };
+                builder.build(mat_ptr)
+            })
+            .collect();
+        Ok(Self { meshes })
+    }
+
+    // all triangles of all meshes, ready for BvhNode::new
+    pub fn triangles(&self) -> HittableList {
+        let mut list = HittableList::default();
+        for obj_mesh in &self.meshes {
+            for triangle in obj_mesh.mesh.clone().triangles().objects() {
+                list.add(triangle.clone());
+            }
+        }
+        list
+    }
+}
+
+// loads the materials of a Wavefront .mtl file, mapped onto the closest of
+// our materials: emissive ones become DiffuseLight, transparent ones
+// Dieletric, specular ones Metal, and everything else Lambertian
+pub fn load_mtl(path: impl AsRef<Path>) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let path = path.as_ref();
+    let file = open(path)?;
+    parse_mtl(BufReader::new(file), path)
+}
+
+pub fn parse_mtl<R: BufRead>(
+    reader: R,
+    path: &Path,
+) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let mut descriptions: Vec<MtlDescription> = vec![];
+
+    for (index, line) in reader.lines().enumerate() {
+        let ctx = LineContext {
+            path,
+            line: index + 1,
+        };
+        let line = line.map_err(|source| ObjError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(ctx.error("newmtl needs a material name"));
+            }
+            descriptions.push(MtlDescription::new(args.join(" ")));
+            continue;
+        }
+        let description = match descriptions.last_mut() {
+            Some(description) => description,
+            None if keyword.starts_with("map_") => continue,
+            None => return Err(ctx.error(&format!("'{}' before any newmtl", keyword))),
+        };
+        match keyword {
+            "Kd" => description.diffuse = ctx.parse_vec3(&args)?,
+            "Ks" => description.specular = ctx.parse_vec3(&args)?,
+            "Ke" => description.emission = ctx.parse_vec3(&args)?,
+            "Ns" => description.shininess = ctx.parse_f64(args.first().copied())?,
+            "Ni" => description.index_of_refraction = ctx.parse_f64(args.first().copied())?,
+            "d" => description.dissolve = ctx.parse_f64(args.first().copied())?,
+            "Tr" => description.dissolve = 1.0 - ctx.parse_f64(args.first().copied())?,
+            "illum" => {
+                description.illum = args
+                    .first()
+                    .and_then(|arg| arg.parse().ok())
+                    .ok_or_else(|| ctx.error("expected an illumination model number"))?
+            }
+            // ambient color, texture maps etc. have no equivalent (yet)
+            _ => {}
+        }
+    }
+
+    Ok(descriptions
+        .into_iter()
+        .map(|description| {
+            let material = description.to_material();
+            (description.name, material)
+        })
+        .collect())
+}
+
+fn open(path: &Path) -> Result<File, ObjError> {
+    File::open(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+struct MtlDescription {
+    name: String,
+    diffuse: Color,
+    specular: Color,
+    emission: Color,
+    shininess: f64,
+    index_of_refraction: f64,
+    dissolve: f64,
+    illum: u32,
+}
+
+impl MtlDescription {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::zero(),
+            emission: Color::zero(),
+            shininess: 0.0,
+            index_of_refraction: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+
+    fn to_material(&self) -> Arc<dyn Material> {
+        let transparent = self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9);
+        let reflective = matches!(self.illum, 3 | 5 | 8)
+            || (self.diffuse.near_zero() && !self.specular.near_zero());
+        if !self.emission.near_zero() {
+            Arc::new(DiffuseLight::new(self.emission))
+        } else if transparent {
+            Arc::new(Dieletric::new(self.index_of_refraction))
+        } else if reflective {
+            // higher Phong exponents give sharper reflections
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            Arc::new(Metal::new(self.specular, fuzz))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse))
+        }
+    }
+}
+
+// 0-based indices of a face vertex's position, uv and normal
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+// accumulates the triangles of one group/material pair, merging the
+// separate position/uv/normal indices of .obj faces into one vertex buffer
+struct MeshBuilder {
+    group: String,
+    material: Option<String>,
+    vertex_ids: HashMap<FaceVertex, usize>,
+    positions: Vec<Point3>,
+    uvs: Vec<Option<(f64, f64)>>,
+    normals: Vec<Option<Vec3>>,
+    indices: Vec<[usize; 3]>,
+}
+
+impl MeshBuilder {
+    fn new(group: &str, material: &Option<String>) -> Self {
+        Self {
+            group: group.to_string(),
+            material: material.clone(),
+            vertex_ids: HashMap::new(),
+            positions: vec![],
+            uvs: vec![],
+            normals: vec![],
+            indices: vec![],
+        }
+    }
+
+    fn vertex(
+        &mut self,
+        face_vertex: FaceVertex,
+        positions: &[Point3],
+        uvs: &[(f64, f64)],
+        normals: &[Vec3],
+    ) -> usize {
+        if let Some(&id) = self.vertex_ids.get(&face_vertex) {
+            return id;
+        }
+        let (p, t, n) = face_vertex;
+        let id = self.positions.len();
+        self.positions.push(positions[p]);
+        self.uvs.push(t.map(|t| uvs[t]));
+        self.normals.push(n.map(|n| normals[n]));
+        self.vertex_ids.insert(face_vertex, id);
+        id
+    }
+
+    // triangulates the (assumed convex) polygon as a fan around its first
+    // vertex
+    fn add_polygon(
+        &mut self,
+        face: &[FaceVertex],
+        positions: &[Point3],
+        uvs: &[(f64, f64)],
+        normals: &[Vec3],
+    ) {
+        let ids: Vec<usize> = face
+            .iter()
+            .map(|&face_vertex| self.vertex(face_vertex, positions, uvs, normals))
+            .collect();
+        for i in 1..ids.len() - 1 {
+            self.indices.push([ids[0], ids[i], ids[i + 1]]);
+        }
+    }
+
+    fn build(self, mat_ptr: Arc<dyn Material>) -> ObjMesh {
+        // smooth shading and texturing need data for every vertex
+        let normals: Option<Vec<Vec3>> = self.normals.into_iter().collect();
+        let uvs: Option<Vec<(f64, f64)>> = self.uvs.into_iter().collect();
+        let mut mesh = TriangleMesh::new(self.positions, normals, self.indices, mat_ptr);
+        if let Some(uvs) = uvs {
+            mesh = mesh.with_uvs(uvs);
+        }
+        ObjMesh {
+            group: self.group,
+            material_name: self.material,
+            mesh: Arc::new(mesh),
+        }
+    }
+}
+
+struct LineContext<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl LineContext<'_> {
+    fn error(&self, message: &str) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message: message.to_string(),
+        }
+    }
+
+    fn parse_f64(&self, token: Option<&str>) -> Result<f64, ObjError> {
+        let token = token.ok_or_else(|| self.error("expected a number"))?;
+        token
+            .parse()
+            .map_err(|_| self.error(&format!("invalid number '{}'", token)))
+    }
+
+    fn parse_vec3(&self, args: &[&str]) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(
+            self.parse_f64(args.first().copied())?,
+            self.parse_f64(args.get(1).copied())?,
+            self.parse_f64(args.get(2).copied())?,
+        ))
+    }
+
+    // resolves a 1-based (or negative, relative to the end) index
+    fn parse_index(&self, token: &str, count: usize, kind: &str) -> Result<usize, ObjError> {
+        let index: i64 = token
+            .parse()
+            .map_err(|_| self.error(&format!("invalid {} index '{}'", kind, token)))?;
+        let resolved = if index < 0 {
+            count as i64 + index
+        } else {
+            index - 1
+        };
+        if resolved < 0 || resolved >= count as i64 {
+            return Err(self.error(&format!("{} index {} out of range", kind, index)));
+        }
+        Ok(resolved as usize)
+    }
+
+    // parses v, v/vt, v//vn or v/vt/vn
+    fn parse_face_vertex(
+        &self,
+        token: &str,
+        position_count: usize,
+        uv_count: usize,
+        normal_count: usize,
+    ) -> Result<FaceVertex, ObjError> {
+        let mut parts = token.split('/');
+        let position = self.parse_index(parts.next().unwrap(), position_count, "vertex")?;
+        let uv = match parts.next() {
+            Some(part) if !part.is_empty() => Some(self.parse_index(part, uv_count, "uv")?),
+            _ => None,
+        };
+        let normal = match parts.next() {
+            Some(part) if !part.is_empty() => {
+                Some(self.parse_index(part, normal_count, "normal")?)
+            }
+            _ => None,
+        };
+        if parts.next().is_some() {
+            return Err(self.error(&format!("invalid face vertex '{}'", token)));
+        }
+        Ok((position, uv, normal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{BvhNode, HitRecord, Hittable, Ray};
+    use std::fs;
+
+    fn parse(source: &str) -> Result<ObjModel, ObjError> {
+        ObjModel::parse(source.as_bytes(), Path::new("test.obj"), grey_lambertian())
+    }
+
+    const QUAD: &str = "
+        # a unit square facing +z, as one polygon
+        v 0 0 0
+        v 1 0 0
+        v 1 1 0
+        v 0 1 0
+        vt 0 0
+        vt 1 0
+        vt 1 1
+        vt 0 1
+        vn 0 0 1
+        f 1/1/1 2/2/1 3/3/1 4/4/1
+    ";
+
+    #[test]
+    fn polygons_are_triangulated() {
+        let model = parse(QUAD).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0].mesh;
+        assert_eq!(mesh.len(), 2);
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.uvs().unwrap()[2], (1.0, 1.0));
+        assert_eq_vec3s(mesh.normals().unwrap()[0], Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn loaded_mesh_is_hittable() {
+        let model = parse(QUAD).unwrap();
+        let bvh = BvhNode::new(model.triangles());
+        let r = Ray::new(Point3::new(0.7, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = bvh.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_f64_eq(rec.t, 1.0);
+        assert!(rec.is_front_face);
+    }
+
+    #[test]
+    fn negative_indices_and_groups() {
+        let model = parse(
+            "
+            g first
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            f -3 -2 -1
+            g second
+            v 0 0 1
+            f 1 2 4
+            f 2 3 4
+        ",
+        )
+        .unwrap();
+        let groups: Vec<_> = model.meshes.iter().map(|m| m.group.as_str()).collect();
+        assert_eq!(groups, vec!["first", "second"]);
+        assert_eq!(model.meshes[0].mesh.len(), 1);
+        assert_eq!(model.meshes[1].mesh.len(), 2);
+        assert!(model.meshes[1].mesh.normals().is_none());
+    }
+
+    #[test]
+    fn errors_report_line_numbers() {
+        let err = parse("v 0 0 0\nv 1 0 0\nv 0 one 0\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.obj:3: invalid number 'one'");
+
+        let err = parse("v 0 0 0\nf 1 2 3\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.obj:2: vertex index 2 out of range");
+
+        let err = parse("usemtl missing\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.obj:1: unknown material 'missing'");
+    }
+
+    #[test]
+    fn missing_file_is_an_error() {
+        let err = ObjModel::load("does/not/exist.obj", grey_lambertian());
+        assert!(matches!(err, Err(ObjError::Io { .. })));
+    }
+
+    // the hit of a ray straight down onto the face at height z
+    fn hit_face_at(model: &ObjModel, z: f64) -> HitRecord {
+        let r = Ray::new(Point3::new(0.2, 0.2, z + 0.5), Vec3::new(0.0, 0.0, -1.0));
+        BvhNode::new(model.triangles()).hit(r, 0.001, 1.0).unwrap()
+    }
+
+    #[test]
+    fn mtl_materials_are_applied() {
+        let dir = std::env::temp_dir().join(format!("raytracing-obj-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("scene.mtl"),
+            "
+            newmtl red
+            Kd 0.8 0.1 0.1
+            newmtl glass
+            Ni 1.45
+            d 0.1
+            newmtl chrome
+            Kd 0 0 0
+            Ks 0.9 0.9 0.9
+            Ns 500
+            ",
+        )
+        .unwrap();
+        // one triangle per material, stacked so that each can be hit from
+        // above: red at z = 0, glass at z = 1 and chrome at z = 2
+        fs::write(
+            dir.join("scene.obj"),
+            "
+            mtllib scene.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            v 0 0 1
+            v 1 0 1
+            v 0 1 1
+            v 0 0 2
+            v 1 0 2
+            v 0 1 2
+            usemtl red
+            f 1 2 3
+            usemtl glass
+            f 4 5 6
+            usemtl chrome
+            f 7 8 9
+            ",
+        )
+        .unwrap();
+        let model = ObjModel::load(dir.join("scene.obj"), grey_lambertian()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<_> = model
+            .meshes
+            .iter()
+            .map(|m| m.material_name.clone().unwrap())
+            .collect();
+        assert_eq!(names, vec!["red", "glass", "chrome"]);
+
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        // Kd becomes a Lambertian with that albedo
+        let rec = hit_face_at(&model, 0.0);
+        assert_eq_vec3s(
+            rec.mat_ptr.eval(&rec, up, up),
+            Color::new(0.8, 0.1, 0.1) / std::f64::consts::PI,
+        );
+
+        // d < 1 becomes glass with index Ni: head on, it reflects with
+        // the Fresnel reflectance ((n - 1) / (n + 1))^2
+        let rec = hit_face_at(&model, 1.0);
+        let sample = rec.mat_ptr.sample(&rec, up, 0.0, (0.5, 0.5)).unwrap();
+        assert!(sample.is_specular);
+        assert_eq_vec3s(sample.wi, up);
+        assert_f64_eq(sample.pdf, (0.45_f64 / 2.45).powi(2));
+
+        // Ks without Kd becomes a Metal with albedo Ks and fuzz
+        // sqrt(2 / (Ns + 2)): the density of the mirror direction is the
+        // fuzz ball's cross-section through its center over its volume
+        let rec = hit_face_at(&model, 2.0);
+        let fuzz = (2.0_f64 / 502.0).sqrt();
+        let pdf = rec.mat_ptr.pdf(&rec, up, up);
+        let expected = ((1.0 + fuzz).powi(3) - (1.0 - fuzz).powi(3))
+            / (4.0 * std::f64::consts::PI * fuzz.powi(3));
+        assert!((pdf - expected).abs() < 1e-9 * expected);
+        let sample = rec.mat_ptr.sample(&rec, up, 0.5, (0.5, 0.5)).unwrap();
+        assert!(!sample.is_specular);
+        assert_eq_vec3s(sample.weight(rec.normal), Color::new(0.9, 0.9, 0.9));
+    }
+
+    #[test]
+    fn missing_mtllib_reports_its_line() {
+        let err = parse("v 0 0 0\nmtllib missing.mtl\n").err().unwrap();
+        assert!(matches!(err, ObjError::Parse { line: 2, .. }));
+        assert!(err
+            .to_string()
+            .starts_with("test.obj:2: cannot read material library 'missing.mtl': "));
+    }
+
+    #[test]
+    fn mtl_errors_report_line_numbers() {
+        let err = parse_mtl("newmtl a\nKd 1 x 1\n".as_bytes(), Path::new("test.mtl"))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "test.mtl:2: invalid number 'x'");
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{Color, ExrPixelType, Framebuffer, ToneMapping};
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Debug)]
+pub enum OutputError {
+    Io(io::Error),
+    Png(png::EncodingError),
+    UnsupportedFormat(String),
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputError::Io(err) => write!(f, "{}", err),
+            OutputError::Png(err) => write!(f, "png encoding failed: {}", err),
+            OutputError::UnsupportedFormat(ext) => {
+                write!(f, "unsupported image format '{}'", ext)
+            }
+        }
+    }
+}
+
+impl Error for OutputError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OutputError::Io(err) => Some(err),
+            OutputError::Png(err) => Some(err),
+            OutputError::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for OutputError {
+    fn from(err: io::Error) -> Self {
+        OutputError::Io(err)
+    }
+}
+
+impl From<png::EncodingError> for OutputError {
+    fn from(err: png::EncodingError) -> Self {
+        OutputError::Png(err)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PngBitDepth {
+    Eight,
+    Sixteen,
+}
+
+impl PngBitDepth {
+    // 16 bits per channel for paths ending in .16.png (e.g. render.16.png),
+    // 8 otherwise
+    pub fn from_path(path: &Path) -> Self {
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("");
+        if stem.ends_with(".16") {
+            PngBitDepth::Sixteen
+        } else {
+            PngBitDepth::Eight
+        }
+    }
+}
+
+impl Framebuffer {
+    // writes the image by file extension: .png (8 bits per channel, or 16
+    // for .16.png) and .ppm go through the tone mapping, the linear .exr (32
+    // bit floats) and .pfm are written unclamped
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+        tone_mapping: &ToneMapping,
+    ) -> Result<(), OutputError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        // check before creating the file, so bad paths don't leave it behind
+        if !matches!(extension.as_str(), "png" | "ppm" | "exr" | "pfm") {
+            return Err(OutputError::UnsupportedFormat(extension));
+        }
+        let out = BufWriter::new(File::create(path)?);
+        match extension.as_str() {
+            "png" => self.write_png(out, PngBitDepth::from_path(path), tone_mapping),
+            "exr" => Ok(self.write_exr(out, ExrPixelType::Float)?),
+            "pfm" => Ok(self.write_pfm(out)?),
+            _ => Ok(self.write_ppm(out, tone_mapping)?),
+        }
+    }
+
+    // RGB PNG tagged as sRGB, so viewers don't apply their own gamma guess
+    pub fn write_png<W: Write>(
+        &self,
+        out: W,
+        bit_depth: PngBitDepth,
+        tone_mapping: &ToneMapping,
+    ) -> Result<(), OutputError> {
+        let mut encoder = png::Encoder::new(out, self.width(), self.height());
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+        let pixels = self.pixels();
+        let data: Vec<u8> = match bit_depth {
+            PngBitDepth::Eight => {
+                encoder.set_depth(png::BitDepth::Eight);
+                pixels
+                    .iter()
+                    .flat_map(|&pixel: &Color| tone_mapping.encode_u8(pixel))
+                    .collect()
+            }
+            PngBitDepth::Sixteen => {
+                encoder.set_depth(png::BitDepth::Sixteen);
+                // png stores 16 bit samples big-endian
+                pixels
+                    .iter()
+                    .flat_map(|&pixel: &Color| tone_mapping.encode_u16(pixel))
+                    .flat_map(u16::to_be_bytes)
+                    .collect()
+            }
+        };
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    // portable float map of the linear radiance: little-endian floats, with
+    // rows stored from the bottom of the image
+    pub fn write_pfm<W: Write>(&self, mut out: W) -> io::Result<()> {
+        write!(out, "PF\n{} {}\n-1.0\n", self.width(), self.height())?;
+        for y in (0..self.height()).rev() {
+            for x in 0..self.width() {
+                let pixel = self.pixel(x, y);
+                for i in 0..3 {
+                    out.write_all(&(pixel[i] as f32).to_le_bytes())?;
+                }
+            }
+        }
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::HdrImage;
+
+    fn framebuffer() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(2, 1);
+        framebuffer.add_sample(0, 0, Color::new(0.25, 1.0, 4.0));
+        framebuffer.add_sample(1, 0, Color::new(0.0, 0.01, 0.5));
+        framebuffer
+    }
+
+    fn decode(data: &[u8]) -> (png::OutputInfo, Vec<u8>, bool) {
+        let mut reader = png::Decoder::new(data).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        buf.truncate(info.buffer_size());
+        let has_srgb = reader.info().srgb.is_some();
+        (info, buf, has_srgb)
+    }
+
+    #[test]
+    fn writes_8_bit_png() {
+        let mut data = vec![];
+        framebuffer()
+            .write_png(&mut data, PngBitDepth::Eight, &ToneMapping::default())
+            .unwrap();
+        let (info, pixels, has_srgb) = decode(&data);
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(info.bit_depth, png::BitDepth::Eight);
+        assert!(has_srgb);
+        assert_eq!(pixels, vec![137, 255, 255, 0, 25, 188]);
+    }
+
+    #[test]
+    fn writes_16_bit_png() {
+        let mut data = vec![];
+        framebuffer()
+            .write_png(&mut data, PngBitDepth::Sixteen, &ToneMapping::default())
+            .unwrap();
+        let (info, pixels, _) = decode(&data);
+        assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+        assert_eq!(pixels.len(), 12);
+        assert_eq!(u16::from_be_bytes([pixels[0], pixels[1]]), 35199);
+        assert_eq!(u16::from_be_bytes([pixels[2], pixels[3]]), 65535);
+    }
+
+    #[test]
+    fn pfm_round_trips() {
+        let mut data = vec![];
+        framebuffer().write_pfm(&mut data).unwrap();
+        let image = HdrImage::read_pfm(data.as_slice()).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        // no clamping or gamma on the way out
+        assert_eq_vec3s(image.pixels[0], Color::new(0.25, 1.0, 4.0));
+        assert_eq_vec3s(image.pixels[1], Color::new(0.0, 0.01f32 as f64, 0.5));
+    }
+
+    #[test]
+    fn save_picks_png_bit_depth_by_extension() {
+        let dir = std::env::temp_dir();
+        for &(name, depth) in &[
+            ("raytracing-output-test.png", png::BitDepth::Eight),
+            ("raytracing-output-test.16.png", png::BitDepth::Sixteen),
+        ] {
+            let path = dir.join(name);
+            framebuffer().save(&path, &ToneMapping::default()).unwrap();
+            let data = std::fs::read(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            let (info, _, _) = decode(&data);
+            assert_eq!(info.bit_depth, depth);
+        }
+    }
+
+    #[test]
+    fn save_rejects_unknown_extension() {
+        let path = std::env::temp_dir().join("raytracing-output-test.gif");
+        let err = framebuffer()
+            .save(&path, &ToneMapping::default())
+            .err()
+            .unwrap();
+        assert!(matches!(err, OutputError::UnsupportedFormat(ext) if ext == "gif"));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::Color;
+
+// how Russian roulette picks the probability of a path surviving another
+// bounce. surviving paths are divided by that probability, so ending paths
+// early adds noise but no bias
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RussianRoulette {
+    // paths only end when they miss, are absorbed or hit the maximum depth
+    Off,
+    // every bounce survives with the same probability
+    Fixed(f64),
+    // paths survive with probability equal to their largest throughput
+    // component, so dim paths, which contribute little, end soonest
+    Throughput,
+}
+
+// how long paths get: never longer than max_depth segments, and never cut
+// short by Russian roulette before min_depth bounces
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathDepth {
+    max_depth: u32,
+    min_depth: u32,
+    roulette: RussianRoulette,
+}
+
+impl PathDepth {
+    // a fixed depth limit without Russian roulette
+    pub fn new(max_depth: u32) -> Self {
+        Self {
+            max_depth,
+            min_depth: 0,
+            roulette: RussianRoulette::Off,
+        }
+    }
+
+    pub fn with_min_depth(mut self, min_depth: u32) -> Self {
+        self.min_depth = min_depth;
+        self
+    }
+
+    pub fn with_russian_roulette(mut self, roulette: RussianRoulette) -> Self {
+        self.roulette = roulette;
+        self
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    pub fn min_depth(&self) -> u32 {
+        self.min_depth
+    }
+
+    pub fn russian_roulette(&self) -> RussianRoulette {
+        self.roulette
+    }
+
+    // chance of a path with the given throughput continuing after `bounce`
+    // (counted from 0)
+    pub fn survival_probability(&self, bounce: u32, throughput: Color) -> f64 {
+        if bounce + 1 < self.min_depth {
+            return 1.0;
+        }
+        let probability = match self.roulette {
+            RussianRoulette::Off => 1.0,
+            RussianRoulette::Fixed(probability) => probability,
+            RussianRoulette::Throughput => throughput.x().max(throughput.y()).max(throughput.z()),
+        };
+        crate::clamp(probability, 0.0, 1.0)
+    }
+
+    // plays Russian roulette with the uniform sample u after `bounce`,
+    // returning the reweighted throughput if the path survives
+    pub fn continue_path(&self, bounce: u32, throughput: Color, u: f64) -> Option<Color> {
+        let probability = self.survival_probability(bounce, throughput);
+        if probability >= 1.0 {
+            Some(throughput)
+        } else if u < probability {
+            Some(throughput / probability)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn roulette_waits_for_min_depth() {
+        let depth = PathDepth::new(10)
+            .with_min_depth(3)
+            .with_russian_roulette(RussianRoulette::Fixed(0.5));
+        let throughput = Color::new(0.2, 0.2, 0.2);
+        assert_f64_eq(depth.survival_probability(0, throughput), 1.0);
+        assert_f64_eq(depth.survival_probability(1, throughput), 1.0);
+        assert_f64_eq(depth.survival_probability(2, throughput), 0.5);
+        assert_eq_vec3s(
+            depth.continue_path(1, throughput, 0.99).unwrap(),
+            throughput,
+        );
+    }
+
+    #[test]
+    fn throughput_roulette_follows_brightest_channel() {
+        let depth = PathDepth::new(10).with_russian_roulette(RussianRoulette::Throughput);
+        assert_f64_eq(
+            depth.survival_probability(0, Color::new(0.1, 0.4, 0.2)),
+            0.4,
+        );
+        // bright paths always survive
+        assert_f64_eq(
+            depth.survival_probability(0, Color::new(3.0, 0.0, 0.0)),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn survivors_are_reweighted() {
+        let depth = PathDepth::new(10).with_russian_roulette(RussianRoulette::Throughput);
+        let throughput = Color::new(0.25, 0.5, 0.125);
+        assert_eq_vec3s(
+            depth.continue_path(0, throughput, 0.3).unwrap(),
+            Color::new(0.5, 1.0, 0.25),
+        );
+        assert!(depth.continue_path(0, throughput, 0.7).is_none());
+        // so the expected throughput is unchanged
+        let survived = (0..1000)
+            .filter_map(|i| depth.continue_path(0, throughput, (i as f64 + 0.5) / 1000.0))
+            .fold(Color::zero(), |sum, t| sum + t);
+        assert_eq_vec3s(survived / 1000.0, throughput);
+    }
+
+    #[test]
+    fn no_roulette_by_default() {
+        let depth = PathDepth::new(5);
+        assert_eq!(depth.russian_roulette(), RussianRoulette::Off);
+        let dim = Color::new(0.01, 0.0, 0.0);
+        assert_eq_vec3s(depth.continue_path(7, dim, 0.99).unwrap(), dim);
+    }
+}
@@ -0,0 +1,391 @@
+use crate::{Color, Pcg32, Point3, Texture, Vec3};
+
+const POINT_COUNT: usize = 256;
+
+// Perlin's gradient noise: random unit gradients on the integer lattice,
+// hashed through per-axis permutations and blended with a smoothstep, as in
+// Ray Tracing: The Next Week. the same seed always gives the same noise
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Pcg32::new(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| Vec3::rand_unit_vector(&mut rng))
+            .collect();
+        Self {
+            gradients,
+            perm_x: Perlin::permutation(&mut rng),
+            perm_y: Perlin::permutation(&mut rng),
+            perm_z: Perlin::permutation(&mut rng),
+        }
+    }
+
+    // Fisher-Yates shuffle of 0..POINT_COUNT
+    fn permutation(rng: &mut Pcg32) -> Vec<usize> {
+        let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+        for i in (1..POINT_COUNT).rev() {
+            let target = (rng.rand_f64() * (i + 1) as f64) as usize;
+            perm.swap(i, target);
+        }
+        perm
+    }
+
+    // smooth noise in [-1, 1], zero at lattice points
+    pub fn noise(&self, p: Point3) -> f64 {
+        let (i, j, k) = (p.x().floor(), p.y().floor(), p.z().floor());
+        let (u, v, w) = (p.x() - i, p.y() - j, p.z() - k);
+        let (i, j, k) = (i as i64, j as i64, k as i64);
+        // wrap the lattice every POINT_COUNT cells
+        let index = |n: i64, d: usize| (n + d as i64).rem_euclid(POINT_COUNT as i64) as usize;
+
+        let mut corners = [[[Vec3::zero(); 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    *corner = self.gradients[self.perm_x[index(i, di)]
+                        ^ self.perm_y[index(j, dj)]
+                        ^ self.perm_z[index(k, dk)]];
+                }
+            }
+        }
+        Perlin::interpolate(&corners, u, v, w)
+    }
+
+    // trilinear blend of the corners' gradient ramps, with Hermite smoothing
+    // so the noise has no visible grid
+    fn interpolate(corners: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+        let (uu, vv, ww) = (smooth(u), smooth(v), smooth(w));
+        let mut accum = 0.0;
+        for (i, plane) in corners.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, gradient) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * Vec3::dot(*gradient, weight);
+                }
+            }
+        }
+        accum
+    }
+
+    // sum of octaves of noise at doubling frequencies and halving amplitudes,
+    // folded to be positive
+    pub fn turbulence(&self, p: Point3, octaves: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            accum += weight * self.noise(p);
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        accum.abs()
+    }
+}
+
+fn grey(value: f64) -> Color {
+    Color::new(value, value, value)
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+// plain noise remapped to [0, 1], at frequency `scale`
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        grey(0.5 * (1.0 + self.noise.noise(self.scale * p)))
+    }
+}
+
+const DEFAULT_OCTAVES: u32 = 7;
+
+// turbulence, like the rough look of clouds or stucco
+pub struct TurbulenceTexture {
+    noise: Perlin,
+    scale: f64,
+    octaves: u32,
+}
+
+impl TurbulenceTexture {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            octaves: DEFAULT_OCTAVES,
+        }
+    }
+
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves;
+        self
+    }
+}
+
+impl Texture for TurbulenceTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        grey(self.noise.turbulence(self.scale * p, self.octaves).min(1.0))
+    }
+}
+
+// stripes along z, whose phase is pushed around by turbulence
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,
+    turbulence: f64,
+    vein: Color,
+    base: Color,
+}
+
+impl MarbleTexture {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            turbulence: 10.0,
+            vein: Color::zero(),
+            base: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    // how far turbulence bends the stripes; 10 by default
+    pub fn with_turbulence(mut self, turbulence: f64) -> Self {
+        self.turbulence = turbulence;
+        self
+    }
+
+    pub fn with_colors(mut self, vein: Color, base: Color) -> Self {
+        self.vein = vein;
+        self.base = base;
+        self
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let phase =
+            self.scale * p.z() + self.turbulence * self.noise.turbulence(p, DEFAULT_OCTAVES);
+        lerp(self.vein, self.base, 0.5 * (1.0 + phase.sin()))
+    }
+}
+
+// growth rings around the y axis, `scale` rings per unit, wobbled by noise
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64,
+    distortion: f64,
+    light: Color,
+    dark: Color,
+}
+
+impl WoodTexture {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            distortion: 0.3,
+            light: Color::new(0.76, 0.6, 0.42),
+            dark: Color::new(0.45, 0.3, 0.16),
+        }
+    }
+
+    // how far noise moves rings, in rings; 0.3 by default
+    pub fn with_distortion(mut self, distortion: f64) -> Self {
+        self.distortion = distortion;
+        self
+    }
+
+    pub fn with_colors(mut self, light: Color, dark: Color) -> Self {
+        self.light = light;
+        self.dark = dark;
+        self
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let radius = (p.x() * p.x() + p.z() * p.z()).sqrt() * self.scale;
+        let rings = radius + self.distortion * self.noise.noise(p * self.scale);
+        // a ring is light wood fading sharply into a thin dark band
+        let t = rings - rings.floor();
+        lerp(self.light, self.dark, t.powi(4))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{Hittable, Lambertian, MovingSphere, Ray, Sphere, Transformed};
+    use std::sync::Arc;
+
+    fn points() -> impl Iterator<Item = Point3> {
+        let mut rng = Pcg32::new(3);
+        (0..1000).map(move |_| Vec3::rand_in_range(&mut rng, -20.0, 20.0))
+    }
+
+    #[test]
+    fn noise_is_bounded_and_vanishes_on_the_lattice() {
+        let perlin = Perlin::new(1);
+        for p in points() {
+            assert!(perlin.noise(p).abs() <= 1.0);
+        }
+        for &p in &[
+            Point3::zero(),
+            Point3::new(3.0, -2.0, 7.0),
+            Point3::new(-300.0, 12.0, 1.0),
+        ] {
+            assert_f64_eq(perlin.noise(p), 0.0);
+        }
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        let perlin = Perlin::new(1);
+        let offset = Vec3::new(1e-6, -1e-6, 1e-6);
+        for p in points() {
+            // crossing cell boundaries too
+            let p = Point3::new(p.x().round(), p.y(), p.z());
+            assert!((perlin.noise(p) - perlin.noise(p + offset)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn seeds_make_noise_reproducible() {
+        let (a, b, c) = (Perlin::new(5), Perlin::new(5), Perlin::new(6));
+        let p = Point3::new(0.3, 1.7, -2.2);
+        assert_eq!(a.noise(p).to_bits(), b.noise(p).to_bits());
+        assert_ne!(a.noise(p), c.noise(p));
+    }
+
+    #[test]
+    fn noise_is_not_flat() {
+        let perlin = Perlin::new(1);
+        let (mut sum, mut sum2) = (0.0, 0.0);
+        for p in points() {
+            let n = perlin.noise(p);
+            sum += n;
+            sum2 += n * n;
+        }
+        let mean = sum / 1000.0;
+        let variance = sum2 / 1000.0 - mean * mean;
+        assert!(mean.abs() < 0.05);
+        assert!(variance > 0.01);
+    }
+
+    #[test]
+    fn textures_stay_between_their_colors() {
+        let textures: Vec<Box<dyn Texture>> = vec![
+            Box::new(NoiseTexture::new(1, 4.0)),
+            Box::new(TurbulenceTexture::new(1, 4.0)),
+            Box::new(MarbleTexture::new(1, 4.0)),
+            Box::new(
+                WoodTexture::new(1, 4.0).with_colors(Color::new(1.0, 1.0, 1.0), Color::zero()),
+            ),
+        ];
+        for texture in &textures {
+            for p in points() {
+                let color = texture.value(0.0, 0.0, p);
+                for i in 0..3 {
+                    assert!((0.0..=1.0).contains(&color[i]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wood_rings_are_centred_on_the_y_axis() {
+        let wood = WoodTexture::new(1, 1.0).with_distortion(0.0);
+        // rings only depend on the distance from the axis
+        let a = wood.value(0.0, 0.0, Point3::new(0.5, 0.0, 0.0));
+        let b = wood.value(0.0, 0.0, Point3::new(0.0, 4.0, -0.5));
+        assert_eq_vec3s(a, b);
+        assert_eq_vec3s(
+            wood.value(0.0, 0.0, Point3::zero()),
+            Color::new(0.76, 0.6, 0.42),
+        );
+    }
+
+    #[test]
+    fn solid_textures_move_with_transformed_objects() {
+        let marbled = || {
+            Sphere::new(
+                Point3::zero(),
+                1.0,
+                Arc::new(Lambertian::from_texture(Arc::new(NoiseTexture::new(
+                    3, 4.0,
+                )))),
+            )
+        };
+        let offset = Vec3::new(10.0, 3.0, -5.0);
+        let moved = Transformed::new(marbled()).translate(offset);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let wo = -down;
+
+        // the same local point, hit on the sphere where it was made and
+        // where it was moved to
+        let local = Point3::new(0.3, 0.0, 0.2);
+        let r = Ray::new(local + Vec3::new(0.0, 5.0, 0.0), down);
+        let here = marbled().hit(r, 0.001, f64::INFINITY).unwrap();
+        let r = Ray::new(local + offset + Vec3::new(0.0, 5.0, 0.0), down);
+        let there = moved.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(there.point, here.point + offset);
+        assert_eq_vec3s(there.local_point, here.point);
+        assert_eq_vec3s(
+            there.mat_ptr.eval(&there, wo, wo),
+            here.mat_ptr.eval(&here, wo, wo),
+        );
+    }
+
+    #[test]
+    fn solid_textures_move_with_moving_spheres() {
+        let offset = Vec3::new(2.0, 0.0, 0.0);
+        let sphere = MovingSphere::new(
+            (Point3::zero(), 0.0),
+            (offset, 1.0),
+            1.0,
+            Arc::new(Lambertian::from_texture(Arc::new(MarbleTexture::new(
+                3, 4.0,
+            )))),
+        );
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let wo = -down;
+
+        // the same surface point, at the start and the end of the shutter
+        let local = Point3::new(0.3, 5.0, 0.2);
+        let start = Ray::new(local, down).with_time(0.0);
+        let start = sphere.hit(start, 0.001, f64::INFINITY).unwrap();
+        let end = Ray::new(local + offset, down).with_time(1.0);
+        let end = sphere.hit(end, 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(end.point, start.point + offset);
+        assert_eq_vec3s(end.local_point, start.local_point);
+        assert_eq_vec3s(
+            end.mat_ptr.eval(&end, wo, wo),
+            start.mat_ptr.eval(&start, wo, wo),
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{sample_concentric_disk, Aabb, HitRecord, Hittable, Material, Onb, Point3, Ray, Vec3};
+use std::{f64::consts::PI, sync::Arc};
+
+// distance along r to the plane through point with unit normal, if the ray
+// isn't parallel to it and the hit is within [t_min, t_max]
+fn hit_plane(point: Point3, normal: Vec3, r: Ray, t_min: f64, t_max: f64) -> Option<f64> {
+    let denominator = Vec3::dot(normal, r.direction());
+    if denominator.abs() < 1e-8 {
+        return None;
+    }
+    let t = Vec3::dot(normal, point - r.origin()) / denominator;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some(t)
+}
+
+// infinite plane through point. it has no bounding box, so a BvhNode tests
+// it against every ray rather than putting it in its tree. uvs are
+// distances along two directions in the plane, so image textures repeat
+// every unit
+pub struct Plane {
+    point: Point3,
+    normal: Vec3,
+    axes: (Vec3, Vec3),
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, mat_ptr: Arc<dyn Material>) -> Self {
+        let normal = Vec3::unit(normal);
+        let onb = Onb::from_w(normal);
+        Self {
+            point,
+            normal,
+            axes: (
+                onb.local(Vec3::new(1.0, 0.0, 0.0)),
+                onb.local(Vec3::new(0.0, 1.0, 0.0)),
+            ),
+            mat_ptr,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = hit_plane(self.point, self.normal, r, t_min, t_max)?;
+        let p = r.at(t);
+        let mut hit_rec = HitRecord {
+            t,
+            point: p,
+            local_point: p,
+            normal: self.normal,
+            u: Vec3::dot(p - self.point, self.axes.0),
+            v: Vec3::dot(p - self.point, self.axes.1),
+            is_front_face: true,
+            mat_ptr: self.mat_ptr.clone(),
+        };
+        hit_rec.set_face_normal(r, self.normal);
+        Some(hit_rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+// flat disk around center, facing along normal. u is the angle around the
+// center as a fraction of a turn, v the distance from it as a fraction of
+// the radius
+pub struct Disk {
+    center: Point3,
+    normal: Vec3,
+    radius: f64,
+    onb: Onb,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, mat_ptr: Arc<dyn Material>) -> Self {
+        let normal = Vec3::unit(normal);
+        Self {
+            center,
+            normal,
+            radius,
+            onb: Onb::from_w(normal),
+            mat_ptr,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = hit_plane(self.center, self.normal, r, t_min, t_max)?;
+        let p = r.at(t);
+        let offset = p - self.center;
+        if offset.length_squared() > self.radius * self.radius {
+            return None;
+        }
+        let x = Vec3::dot(offset, self.onb.local(Vec3::new(1.0, 0.0, 0.0)));
+        let y = Vec3::dot(offset, self.onb.local(Vec3::new(0.0, 1.0, 0.0)));
+        let mut hit_rec = HitRecord {
+            t,
+            point: p,
+            local_point: p,
+            normal: self.normal,
+            u: y.atan2(x).rem_euclid(2.0 * PI) / (2.0 * PI),
+            v: offset.length() / self.radius,
+            is_front_face: true,
+            mat_ptr: self.mat_ptr.clone(),
+        };
+        hit_rec.set_face_normal(r, self.normal);
+        Some(hit_rec)
+    }
+
+    // along each axis the disk reaches radius * sin of the angle between the
+    // axis and the normal
+    fn bounding_box(&self) -> Option<Aabb> {
+        let n = self.normal;
+        let reach = |component: f64| self.radius * (1.0 - component * component).max(0.0).sqrt();
+        let padding = 1e-4;
+        let extent = Vec3::new(
+            reach(n.x()) + padding,
+            reach(n.y()) + padding,
+            reach(n.z()) + padding,
+        );
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    // points are sampled uniformly by area, so the density per unit solid
+    // angle is distance^2 / (|cos| * area)
+    fn pdf_value(&self, origin: Point3, direction: Vec3, _time: f64) -> f64 {
+        let rec = match self.hit(Ray::new(origin, direction), 0.001, f64::INFINITY) {
+            Some(rec) => rec,
+            None => return 0.0,
+        };
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = Vec3::dot(direction, rec.normal).abs() / direction.length();
+        distance_squared / (cosine * PI * self.radius * self.radius)
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), _time: f64) -> Vec3 {
+        let (x, y) = sample_concentric_disk(u);
+        self.center + self.radius * self.onb.local(Vec3::new(x, y, 0.0)) - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn plane_is_hit_everywhere_but_unbounded() {
+        let plane = Plane::new(Point3::zero(), Vec3::new(0.0, 2.0, 0.0), grey_lambertian());
+        assert!(plane.bounding_box().is_none());
+        let r = Ray::new(Point3::new(500.0, 3.0, -800.0), Vec3::new(1.0, -1.0, 0.0));
+        let rec = plane.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_f64_eq(rec.t, 3.0);
+        assert_eq_vec3s(rec.point, Point3::new(503.0, 0.0, -800.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        // uvs measure distances in the plane
+        assert_f64_eq(rec.u * rec.u + rec.v * rec.v, 503.0 * 503.0 + 800.0 * 800.0);
+        let r = Ray::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(plane.hit(r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn disk_is_round() {
+        let disk = Disk::new(
+            Point3::new(0.0, 0.0, -2.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            grey_lambertian(),
+        );
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        let rec = disk
+            .hit(
+                Ray::new(Point3::new(0.6, 0.6, 0.0), down),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert_f64_eq(rec.v, 0.72_f64.sqrt());
+        assert!(disk
+            .hit(
+                Ray::new(Point3::new(0.8, 0.8, 0.0), down),
+                0.001,
+                f64::INFINITY
+            )
+            .is_none());
+
+        let bbox = disk.bounding_box().unwrap();
+        assert!((bbox.max().x() - 1.0).abs() < 1e-3);
+        assert!((bbox.max().z() + 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn disk_light_samples_land_on_it() {
+        let disk = Disk::new(
+            Point3::new(0.0, 3.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            0.5,
+            grey_lambertian(),
+        );
+        let origin = Point3::zero();
+        for &u in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.7)] {
+            let direction = disk.random(origin, u, 0.0);
+            assert!(disk
+                .hit(Ray::new(origin, direction), 0.001, 1.001)
+                .is_some());
+        }
+        // straight on, the density is distance^2 / area
+        let pdf = disk.pdf_value(origin, Vec3::new(0.0, 1.0, 0.0), 0.0);
+        assert_f64_eq(pdf, 9.0 / (PI * 0.25));
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{Aabb, HitRecord, Hittable, HittableList, Material, Point3, Ray, Vec3};
+use std::sync::Arc;
+
+// parallelogram with corner q and sides u and v, facing along u x v. uvs
+// run from 0 to 1 along the sides
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    // plane offset: points p on the quad have dot(normal, p) = d
+    d: f64,
+    // u x v / |u x v|^2, for recovering a point's coordinates along u and v
+    w: Vec3,
+    area: f64,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat_ptr: Arc<dyn Material>) -> Self {
+        let n = Vec3::cross(u, v);
+        let normal = Vec3::unit(n);
+        Self {
+            q,
+            u,
+            v,
+            normal,
+            d: Vec3::dot(normal, q),
+            w: n / n.length_squared(),
+            area: n.length(),
+            mat_ptr,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denominator = Vec3::dot(self.normal, r.direction());
+        // parallel to the plane
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.d - Vec3::dot(self.normal, r.origin())) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let p = r.at(t);
+        let planar = p - self.q;
+        let alpha = Vec3::dot(self.w, Vec3::cross(planar, self.v));
+        let beta = Vec3::dot(self.w, Vec3::cross(self.u, planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut hit_rec = HitRecord {
+            t,
+            point: p,
+            local_point: p,
+            normal: self.normal,
+            u: alpha,
+            v: beta,
+            is_front_face: true,
+            mat_ptr: self.mat_ptr.clone(),
+        };
+        hit_rec.set_face_normal(r, self.normal);
+        Some(hit_rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // pad the box so that axis-aligned quads don't get a flat box
+        let padding = Vec3::new(1e-4, 1e-4, 1e-4);
+        let corners = [self.q + self.u, self.q + self.v, self.q + self.u + self.v];
+        let bbox = corners.iter().fold(Aabb::new(self.q, self.q), |bbox, &p| {
+            Aabb::surrounding_box(bbox, Aabb::new(p, p))
+        });
+        Some(Aabb::new(bbox.min() - padding, bbox.max() + padding))
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    // points are sampled uniformly by area, so the density per unit solid
+    // angle is distance^2 / (|cos| * area)
+    fn pdf_value(&self, origin: Point3, direction: Vec3, _time: f64) -> f64 {
+        let rec = match self.hit(Ray::new(origin, direction), 0.001, f64::INFINITY) {
+            Some(rec) => rec,
+            None => return 0.0,
+        };
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = Vec3::dot(direction, rec.normal).abs() / direction.length();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), _time: f64) -> Vec3 {
+        self.q + u.0 * self.u + u.1 * self.v - origin
+    }
+}
+
+// axis-aligned box between two opposite corners, made of six quads facing
+// out
+pub struct BoxShape {
+    sides: HittableList,
+    bbox: Aabb,
+}
+
+impl BoxShape {
+    pub fn new(a: Point3, b: Point3, mat_ptr: Arc<dyn Material>) -> Self {
+        let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+        let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+        let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+        let faces = [
+            (Point3::new(min.x(), min.y(), max.z()), dx, dy), // front
+            (Point3::new(max.x(), min.y(), max.z()), -dz, dy), // right
+            (Point3::new(max.x(), min.y(), min.z()), -dx, dy), // back
+            (Point3::new(min.x(), min.y(), min.z()), dz, dy), // left
+            (Point3::new(min.x(), max.y(), max.z()), dx, -dz), // top
+            (Point3::new(min.x(), min.y(), min.z()), dx, dz), // bottom
+        ];
+        let mut sides = HittableList::default();
+        for &(q, u, v) in &faces {
+            sides.add(Arc::new(Quad::new(q, u, v, mat_ptr.clone())));
+        }
+        Self {
+            sides,
+            bbox: Aabb::new(min, max),
+        }
+    }
+}
+
+impl Hittable for BoxShape {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.sides.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        self.sides.pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), time: f64) -> Vec3 {
+        self.sides.random(origin, u, time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    // 2 x 1 quad in the z = -1 plane, facing +z
+    fn quad() -> Quad {
+        Quad::new(
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            grey_lambertian(),
+        )
+    }
+
+    #[test]
+    fn quad_hits_have_uvs() {
+        let r = Ray::new(Point3::new(1.5, 0.25, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = quad().hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_f64_eq(rec.t, 1.0);
+        assert_f64_eq(rec.u, 0.75);
+        assert_f64_eq(rec.v, 0.25);
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.is_front_face);
+
+        let r = Ray::new(Point3::new(1.5, 0.25, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = quad().hit(r, 0.001, f64::INFINITY).unwrap();
+        assert!(!rec.is_front_face);
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn quad_misses_outside_and_parallel() {
+        let r = Ray::new(Point3::new(2.5, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(quad().hit(r, 0.001, f64::INFINITY).is_none());
+        let r = Ray::new(Point3::new(1.0, 0.5, -1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(quad().hit(r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn quad_light_samples_land_on_it() {
+        let quad = quad();
+        let origin = Point3::new(0.5, 0.5, 0.0);
+        for &u in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.7)] {
+            let direction = quad.random(origin, u, 0.0);
+            assert!(quad
+                .hit(Ray::new(origin, direction), 0.001, 1.001)
+                .is_some());
+        }
+        // straight on, at distance 1, the density is 1 / area
+        assert_f64_eq(quad.pdf_value(origin, Vec3::new(0.0, 0.0, -1.0), 0.0), 0.5);
+        assert_eq!(quad.pdf_value(origin, Vec3::new(0.0, 0.0, 1.0), 0.0), 0.0);
+    }
+
+    #[test]
+    fn box_sides_face_out() {
+        let shape = BoxShape::new(
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(-1.0, -1.0, -1.0),
+            grey_lambertian(),
+        );
+        let bbox = shape.bounding_box().unwrap();
+        assert_eq_vec3s(bbox.min(), Point3::new(-1.0, -1.0, -1.0));
+        assert_eq_vec3s(bbox.max(), Point3::new(1.0, 1.0, 1.0));
+        let axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        for &axis in &axes {
+            for &sign in &[1.0, -1.0] {
+                let offset = Vec3::new(0.1, 0.2, 0.3);
+                let offset = offset - Vec3::dot(offset, axis) * axis;
+                let outside = 3.0 * sign * axis + offset;
+                let r = Ray::new(outside, -sign * axis);
+                let rec = shape.hit(r, 0.001, f64::INFINITY).unwrap();
+                assert_f64_eq(rec.t, 2.0);
+                assert!(rec.is_front_face);
+                assert_eq_vec3s(rec.normal, sign * axis);
+            }
+        }
+    }
+}
@@ -0,0 +1,520 @@
+use crate::{Aabb, HitRecord, Hittable, Material, Point3, Ray, Vec3};
+use std::{f64::consts::PI, sync::Arc};
+
+// analytic surfaces of revolution around the y axis, in a canonical pose:
+// they start at y = 0 and are placed in the world with Transformed
+
+// real roots of a t^2 + b t + c = 0 in increasing order, avoiding the
+// cancellation of the textbook formula. a linear equation (a = 0) has its
+// one root returned twice
+pub(crate) fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let t0 = q / a;
+    let t1 = if q != 0.0 { c / q } else { t0 };
+    Some(if t0 < t1 { (t0, t1) } else { (t1, t0) })
+}
+
+// u coordinate of the angle around the y axis, with the same convention as
+// Sphere: starting from -x, once around
+pub(crate) fn angle_around_y(p: Point3) -> f64 {
+    ((-p.z()).atan2(p.x()) + PI) / (2.0 * PI)
+}
+
+// keeps the nearest of several candidate hits along a ray
+pub(crate) struct NearestHit {
+    t_min: f64,
+    t_max: f64,
+    best: Option<(f64, Vec3, (f64, f64))>,
+}
+
+impl NearestHit {
+    pub(crate) fn new(t_min: f64, t_max: f64) -> Self {
+        Self {
+            t_min,
+            t_max,
+            best: None,
+        }
+    }
+
+    pub(crate) fn in_range(&self, t: f64) -> bool {
+        t >= self.t_min && t <= self.t_max
+    }
+
+    // a hit at t with the given outward normal and uv; it's up to the caller
+    // to check that t is on the bounded part of the surface
+    pub(crate) fn offer(&mut self, t: f64, outward_normal: Vec3, uv: (f64, f64)) {
+        if self.in_range(t) {
+            self.t_max = t;
+            self.best = Some((t, outward_normal, uv));
+        }
+    }
+
+    pub(crate) fn into_record(self, r: Ray, mat_ptr: &Arc<dyn Material>) -> Option<HitRecord> {
+        let (t, outward_normal, (u, v)) = self.best?;
+        let outward_normal = Vec3::unit(outward_normal);
+        let point = r.at(t);
+        let mut hit_rec = HitRecord {
+            t,
+            point,
+            local_point: point,
+            normal: outward_normal,
+            u,
+            v,
+            is_front_face: true,
+            mat_ptr: mat_ptr.clone(),
+        };
+        hit_rec.set_face_normal(r, outward_normal);
+        Some(hit_rec)
+    }
+}
+
+// where the ray crosses the horizontal disk of the given radius at height y
+fn offer_cap(nearest: &mut NearestHit, r: Ray, y: f64, radius: f64, outward_normal: Vec3) {
+    if r.direction().y() == 0.0 {
+        return;
+    }
+    let t = (y - r.origin().y()) / r.direction().y();
+    let p = r.at(t);
+    if p.x() * p.x() + p.z() * p.z() <= radius * radius {
+        // planar uvs across the cap
+        let uv = (0.5 * (p.x() / radius + 1.0), 0.5 * (p.z() / radius + 1.0));
+        nearest.offer(t, outward_normal, uv);
+    }
+}
+
+fn upright_box(radius: f64, bottom: f64, top: f64) -> Aabb {
+    Aabb::new(
+        Point3::new(-radius, bottom, -radius),
+        Point3::new(radius, top, radius),
+    )
+}
+
+// cylinder of the given radius from y = 0 to y = height, closed by disks at
+// both ends unless made open with with_caps(false). on the side, u goes
+// around the axis and v up it
+pub struct Cylinder {
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(radius: f64, height: f64, mat_ptr: Arc<dyn Material>) -> Self {
+        Self {
+            radius,
+            height,
+            capped: true,
+            mat_ptr,
+        }
+    }
+
+    pub fn with_caps(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin(), r.direction());
+        let mut nearest = NearestHit::new(t_min, t_max);
+        let a = d.x() * d.x() + d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z());
+        let c = o.x() * o.x() + o.z() * o.z() - self.radius * self.radius;
+        // rays parallel to the axis only ever hit the caps
+        if a != 0.0 {
+            if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+                for &t in &[t0, t1] {
+                    let p = r.at(t);
+                    if (0.0..=self.height).contains(&p.y()) {
+                        let normal = Vec3::new(p.x(), 0.0, p.z());
+                        nearest.offer(t, normal, (angle_around_y(p), p.y() / self.height));
+                    }
+                }
+            }
+        }
+        if self.capped {
+            let up = Vec3::new(0.0, 1.0, 0.0);
+            offer_cap(&mut nearest, r, 0.0, self.radius, -up);
+            offer_cap(&mut nearest, r, self.height, self.radius, up);
+        }
+        nearest.into_record(r, &self.mat_ptr)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(upright_box(self.radius, 0.0, self.height))
+    }
+}
+
+// cone with a base of the given radius at y = 0 and its apex at y = height,
+// closed by a disk at the base unless made open with with_cap(false)
+pub struct Cone {
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(radius: f64, height: f64, mat_ptr: Arc<dyn Material>) -> Self {
+        Self {
+            radius,
+            height,
+            capped: true,
+            mat_ptr,
+        }
+    }
+
+    pub fn with_cap(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin(), r.direction());
+        let mut nearest = NearestHit::new(t_min, t_max);
+        // x^2 + z^2 = k^2 (height - y)^2, with k the slope of the side
+        let k2 = (self.radius / self.height).powi(2);
+        let above = self.height - o.y();
+        let a = d.x() * d.x() + d.z() * d.z() - k2 * d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z() + k2 * above * d.y());
+        let c = o.x() * o.x() + o.z() * o.z() - k2 * above * above;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for &t in &[t0, t1] {
+                let p = r.at(t);
+                if (0.0..=self.height).contains(&p.y()) {
+                    // gradient of the implicit function, which vanishes at
+                    // the apex, where straight up will do
+                    let normal = Vec3::new(p.x(), k2 * (self.height - p.y()), p.z());
+                    let normal = if normal.near_zero() {
+                        Vec3::new(0.0, 1.0, 0.0)
+                    } else {
+                        normal
+                    };
+                    nearest.offer(t, normal, (angle_around_y(p), p.y() / self.height));
+                }
+            }
+        }
+        if self.capped {
+            offer_cap(&mut nearest, r, 0.0, self.radius, Vec3::new(0.0, -1.0, 0.0));
+        }
+        nearest.into_record(r, &self.mat_ptr)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(upright_box(self.radius, 0.0, self.height))
+    }
+}
+
+// bowl y = height (x^2 + z^2) / radius^2 with its vertex at the origin, cut
+// off at y = height where it is `radius` wide, and closed there by a disk
+// unless made open with with_cap(false)
+pub struct Paraboloid {
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Paraboloid {
+    pub fn new(radius: f64, height: f64, mat_ptr: Arc<dyn Material>) -> Self {
+        Self {
+            radius,
+            height,
+            capped: true,
+            mat_ptr,
+        }
+    }
+
+    pub fn with_cap(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+}
+
+impl Hittable for Paraboloid {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin(), r.direction());
+        let mut nearest = NearestHit::new(t_min, t_max);
+        let q = self.height / (self.radius * self.radius);
+        let a = q * (d.x() * d.x() + d.z() * d.z());
+        let b = 2.0 * q * (o.x() * d.x() + o.z() * d.z()) - d.y();
+        let c = q * (o.x() * o.x() + o.z() * o.z()) - o.y();
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for &t in &[t0, t1] {
+                let p = r.at(t);
+                if p.y() <= self.height {
+                    let normal = Vec3::new(2.0 * q * p.x(), -1.0, 2.0 * q * p.z());
+                    nearest.offer(t, normal, (angle_around_y(p), p.y() / self.height));
+                }
+            }
+        }
+        if self.capped {
+            offer_cap(
+                &mut nearest,
+                r,
+                self.height,
+                self.radius,
+                Vec3::new(0.0, 1.0, 0.0),
+            );
+        }
+        nearest.into_record(r, &self.mat_ptr)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(upright_box(self.radius, 0.0, self.height))
+    }
+}
+
+// all points within radius of the segment from the origin to (0, height, 0):
+// a cylinder with hemispheres on both ends. v runs from the bottom of the
+// lower hemisphere to the top of the upper one
+pub struct Capsule {
+    radius: f64,
+    height: f64,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Capsule {
+    pub fn new(radius: f64, height: f64, mat_ptr: Arc<dyn Material>) -> Self {
+        Self {
+            radius,
+            height,
+            mat_ptr,
+        }
+    }
+
+    fn uv(&self, p: Point3) -> (f64, f64) {
+        (
+            angle_around_y(p),
+            (p.y() + self.radius) / (self.height + 2.0 * self.radius),
+        )
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin(), r.direction());
+        let mut nearest = NearestHit::new(t_min, t_max);
+        let r2 = self.radius * self.radius;
+
+        let a = d.x() * d.x() + d.z() * d.z();
+        if a != 0.0 {
+            let b = 2.0 * (o.x() * d.x() + o.z() * d.z());
+            let c = o.x() * o.x() + o.z() * o.z() - r2;
+            if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+                for &t in &[t0, t1] {
+                    let p = r.at(t);
+                    if (0.0..=self.height).contains(&p.y()) {
+                        nearest.offer(t, Vec3::new(p.x(), 0.0, p.z()), self.uv(p));
+                    }
+                }
+            }
+        }
+
+        // each hemisphere is the part of its sphere beyond the cylinder
+        for &(y, below) in &[(0.0, true), (self.height, false)] {
+            let center = Point3::new(0.0, y, 0.0);
+            let oc = o - center;
+            let solution = solve_quadratic(
+                d.length_squared(),
+                2.0 * Vec3::dot(d, oc),
+                oc.length_squared() - r2,
+            );
+            if let Some((t0, t1)) = solution {
+                for &t in &[t0, t1] {
+                    let p = r.at(t);
+                    if (below && p.y() <= y) || (!below && p.y() >= y) {
+                        nearest.offer(t, p - center, self.uv(p));
+                    }
+                }
+            }
+        }
+        nearest.into_record(r, &self.mat_ptr)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(upright_box(
+            self.radius,
+            -self.radius,
+            self.height + self.radius,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    fn hit(object: &dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        object.hit(Ray::new(origin, direction), 0.001, f64::INFINITY)
+    }
+
+    #[test]
+    fn quadratic_roots_are_sorted_and_stable() {
+        let (t0, t1) = solve_quadratic(1.0, -3.0, 2.0).unwrap();
+        assert_f64_eq(t0, 1.0);
+        assert_f64_eq(t1, 2.0);
+        let (t0, t1) = solve_quadratic(-1.0, 3.0, -2.0).unwrap();
+        assert_f64_eq(t0, 1.0);
+        assert_f64_eq(t1, 2.0);
+        // a small root next to a huge one
+        let (t0, t1) = solve_quadratic(1.0, -1e8, 1.0).unwrap();
+        assert_f64_eq(t0, 1e-8);
+        assert_f64_eq(t1, 1e8);
+        assert!(solve_quadratic(1.0, 0.0, 1.0).is_none());
+        assert_eq!(solve_quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+    }
+
+    #[test]
+    fn cylinder_side_and_caps() {
+        let cylinder = Cylinder::new(1.0, 2.0, grey_lambertian());
+        let rec = hit(
+            &cylinder,
+            Point3::new(-5.0, 1.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(-1.0, 1.0, 0.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!(rec.is_front_face);
+        assert_f64_eq(rec.u, 0.0);
+        assert_f64_eq(rec.v, 0.5);
+
+        let rec = hit(
+            &cylinder,
+            Point3::new(0.5, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(0.5, 2.0, 0.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        // without caps, the ray falls through to the inside of the tube
+        let open = Cylinder::new(1.0, 2.0, grey_lambertian()).with_caps(false);
+        assert!(hit(&open, Point3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).is_none());
+        let rec = hit(&open, Point3::new(0.0, 2.5, 0.0), Vec3::new(1.0, -1.0, 0.0)).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(1.0, 1.5, 0.0));
+        assert!(!rec.is_front_face);
+        assert_eq_vec3s(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn cone_normals_lean_up() {
+        let cone = Cone::new(1.0, 1.0, grey_lambertian());
+        let rec = hit(&cone, Point3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(-0.5, 0.5, 0.0));
+        assert_eq_vec3s(rec.normal, Vec3::unit(Vec3::new(-1.0, 1.0, 0.0)));
+        // above the apex there's nothing
+        assert!(hit(&cone, Point3::new(-5.0, 1.5, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+        let rec = hit(&cone, Point3::new(0.2, -3.0, 0.1), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn paraboloid_is_a_bowl() {
+        let bowl = Paraboloid::new(2.0, 4.0, grey_lambertian()).with_cap(false);
+        // looking down into the bowl hits its inside
+        let rec = hit(
+            &bowl,
+            Point3::new(1.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(1.0, 1.0, 0.0));
+        assert!(!rec.is_front_face);
+        // gradient (2 q x, -1, 2 q z) with q = 1, flipped towards the ray
+        assert_eq_vec3s(rec.normal, Vec3::unit(Vec3::new(-2.0, 1.0, 0.0)));
+
+        let capped = Paraboloid::new(2.0, 4.0, grey_lambertian());
+        let rec = hit(
+            &capped,
+            Point3::new(1.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(1.0, 4.0, 0.0));
+        assert!(rec.is_front_face);
+    }
+
+    #[test]
+    fn capsule_has_round_ends() {
+        let capsule = Capsule::new(0.5, 2.0, grey_lambertian());
+        let rec = hit(
+            &capsule,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(0.0, 2.5, 0.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert_f64_eq(rec.v, 1.0);
+
+        let rec = hit(
+            &capsule,
+            Point3::new(0.0, -10.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(0.0, -0.5, 0.0));
+
+        let rec = hit(
+            &capsule,
+            Point3::new(5.0, 1.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(0.5, 1.0, 0.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(1.0, 0.0, 0.0));
+
+        // from inside, the far side faces back at the ray
+        let rec = hit(
+            &capsule,
+            Point3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(0.0, 2.5, 0.0));
+        assert!(!rec.is_front_face);
+    }
+
+    #[test]
+    fn boxes_contain_hits() {
+        let objects: Vec<Box<dyn Hittable>> = vec![
+            Box::new(Cylinder::new(1.0, 2.0, grey_lambertian())),
+            Box::new(Cone::new(1.0, 2.0, grey_lambertian())),
+            Box::new(Paraboloid::new(1.0, 2.0, grey_lambertian())),
+            Box::new(Capsule::new(1.0, 2.0, grey_lambertian())),
+        ];
+        let mut rng = crate::Pcg32::new(9);
+        for object in &objects {
+            let bbox = object.bounding_box().unwrap();
+            for _ in 0..200 {
+                let origin = 5.0 * crate::sample_uniform_sphere((rng.rand_f64(), rng.rand_f64()));
+                let target = Vec3::rand_in_range(&mut rng, -1.0, 1.0) + Vec3::new(0.0, 1.0, 0.0);
+                if let Some(rec) = hit(object.as_ref(), origin, target - origin) {
+                    let p = rec.point;
+                    for axis in 0..3 {
+                        assert!(p[axis] >= bbox.min()[axis] - 1e-9);
+                        assert!(p[axis] <= bbox.max()[axis] + 1e-9);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -4,11 +4,23 @@ use crate::{Point3, Vec3};
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
+    time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    // the moment the ray exists at, for objects that move while the shutter
+    // is open
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
     }
 
     pub fn origin(&self) -> Point3 {
@@ -19,6 +31,10 @@ impl Ray {
         self.direction
     }
 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
     pub fn at(&self, t: f64) -> Point3 {
         self.origin + (t * self.direction)
     }
@@ -49,6 +65,13 @@ mod tests {
             let ray = Ray::new(v1, v2);
             assert_eq_vec3s(ray.origin(), v1);
             assert_eq_vec3s(ray.direction(), v2);
+            assert_eq!(ray.time(), 0.0);
+        }
+
+        #[test]
+        fn with_time(ray in arb_ray(), time in nf64()) {
+            assert_eq!(ray.with_time(time).time(), time);
+            assert_eq_vec3s(ray.with_time(time).origin(), ray.origin());
         }
 
         #[test]
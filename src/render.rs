@@ -0,0 +1,699 @@
+use crate::{
+    Camera, Color, Environment, Framebuffer, HitRecord, Hittable, HittableList, IndependentSampler,
+    PathDepth, Ray, RussianRoulette, Sampler, SkyGradient, Vec3,
+};
+use rayon::prelude::*;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+// estimates the color (radiance) arriving along a camera ray
+pub trait Integrator: Send + Sync {
+    fn ray_color(
+        &self,
+        r: Ray,
+        world: &dyn Hittable,
+        depth: PathDepth,
+        sampler: &mut dyn Sampler,
+    ) -> Color;
+}
+
+// the random walk from the book: follow scattered rays until they are
+// absorbed, cut short by the depth limit or Russian roulette, or escape into
+// the environment, picking up the light emitted by every surface along the
+// way. the path's throughput (the product of the attenuations so far) is
+// carried along instead of recursing
+pub struct PathTracer {
+    environment: Arc<dyn Environment>,
+}
+
+impl PathTracer {
+    pub fn new(environment: Arc<dyn Environment>) -> Self {
+        Self { environment }
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        Self::new(Arc::new(SkyGradient::default()))
+    }
+}
+
+impl Integrator for PathTracer {
+    fn ray_color(
+        &self,
+        r: Ray,
+        world: &dyn Hittable,
+        depth: PathDepth,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let mut radiance = Color::zero();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut r = r;
+        for bounce in 0..depth.max_depth() {
+            let rec = match world.hit(r, 0.001, f64::INFINITY) {
+                Some(rec) => rec,
+                None => {
+                    radiance += throughput * self.environment.color(r.direction());
+                    break;
+                }
+            };
+            radiance += throughput * rec.mat_ptr.emitted(&rec);
+            let reflectance = match rec.mat_ptr.scatter(r, rec.clone(), sampler) {
+                Some(reflectance) => reflectance,
+                None => break,
+            };
+            throughput = match depth.continue_path(
+                bounce,
+                throughput * reflectance.attenuation,
+                sampler.get_1d(),
+            ) {
+                Some(throughput) => throughput,
+                None => break,
+            };
+            r = reflectance.scattered_ray;
+        }
+        radiance
+    }
+}
+
+// path tracer with next-event estimation: at every non-specular surface it
+// also sends a shadow ray towards a sampled light (one of `lights`, or the
+// environment), and weighs that against reaching the same light by following
+// the BSDF with the power heuristic (multiple importance sampling). mirrors
+// and glass can't be light sampled, so light reached through them is only
+// found by following the BSDF, at full weight
+pub struct MisPathTracer {
+    environment: Arc<dyn Environment>,
+    lights: HittableList,
+    sample_environment: bool,
+}
+
+impl MisPathTracer {
+    // lights are the emissive objects worth aiming shadow rays at, and must
+    // also be part of the world. emitters left out are still found by
+    // following the BSDF, as are those that can't be sampled (see
+    // Hittable::can_sample), which are dropped from lights
+    pub fn new(environment: Arc<dyn Environment>, lights: HittableList) -> Self {
+        let mut sampleable = HittableList::default();
+        for light in lights.objects() {
+            if light.can_sample() {
+                sampleable.add(light.clone());
+            }
+        }
+        Self {
+            environment,
+            lights: sampleable,
+            sample_environment: true,
+        }
+    }
+
+    // whether shadow rays are also aimed at the environment, which is wasted
+    // work in closed rooms or against a black background
+    pub fn with_environment_sampling(mut self, sample_environment: bool) -> Self {
+        self.sample_environment = sample_environment;
+        self
+    }
+
+    // probabilities of aiming a shadow ray at the lights or the environment
+    fn light_selection(&self) -> (f64, f64) {
+        let has_lights = !self.lights.objects().is_empty();
+        match (has_lights, self.sample_environment) {
+            (true, true) => (0.5, 0.5),
+            (true, false) => (1.0, 0.0),
+            (false, true) => (0.0, 1.0),
+            (false, false) => (0.0, 0.0),
+        }
+    }
+
+    // density with which light sampling picks direction from origin at time
+    fn light_pdf(&self, origin: Vec3, direction: Vec3, time: f64) -> f64 {
+        let (p_lights, p_environment) = self.light_selection();
+        let mut pdf = 0.0;
+        if p_lights > 0.0 {
+            pdf += p_lights * self.lights.pdf_value(origin, direction, time);
+        }
+        if p_environment > 0.0 {
+            pdf += p_environment * self.environment.pdf(Vec3::unit(direction));
+        }
+        pdf
+    }
+
+    // MIS weight of light found by following the BSDF from the ray's origin
+    fn bsdf_weight(&self, r: Ray, specular_bounce: bool, bsdf_pdf: f64) -> f64 {
+        if specular_bounce {
+            return 1.0;
+        }
+        power_heuristic(
+            bsdf_pdf,
+            self.light_pdf(r.origin(), r.direction(), r.time()),
+        )
+    }
+
+    // light arriving at the hit directly from a sampled light, weighted
+    // against the chance of the BSDF picking the same direction
+    fn sample_light(
+        &self,
+        world: &dyn Hittable,
+        rec: &HitRecord,
+        wo: Vec3,
+        time: f64,
+        choice: f64,
+        u: (f64, f64),
+    ) -> Color {
+        let (p_lights, p_environment) = self.light_selection();
+        if p_lights + p_environment == 0.0 {
+            return Color::zero();
+        }
+        let direction = if choice < p_lights {
+            self.lights.random(rec.point, u, time)
+        } else {
+            self.environment.sample(u).0
+        };
+        let wi = Vec3::unit(direction);
+        let f = rec.mat_ptr.eval(rec, wo, wi) * Vec3::dot(wi, rec.normal).abs();
+        let light_pdf = self.light_pdf(rec.point, wi, time);
+        if f.near_zero() || light_pdf == 0.0 {
+            return Color::zero();
+        }
+        // the shadow ray either ends on an emitter (or something dark in the
+        // way), or escapes to the environment
+        let incoming = match world.hit(
+            Ray::new(rec.point, wi).with_time(time),
+            0.001,
+            f64::INFINITY,
+        ) {
+            Some(light_rec) => light_rec.mat_ptr.emitted(&light_rec),
+            None => self.environment.color(wi),
+        };
+        let weight = power_heuristic(light_pdf, rec.mat_ptr.pdf(rec, wo, wi));
+        f * incoming * (weight / light_pdf)
+    }
+}
+
+impl Integrator for MisPathTracer {
+    fn ray_color(
+        &self,
+        r: Ray,
+        world: &dyn Hittable,
+        depth: PathDepth,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let mut radiance = Color::zero();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut r = r;
+        // like a mirror, the camera can't be light sampled
+        let mut specular_bounce = true;
+        let mut bsdf_pdf = 0.0;
+
+        for bounce in 0..depth.max_depth() {
+            let rec = match world.hit(r, 0.001, f64::INFINITY) {
+                Some(rec) => rec,
+                None => {
+                    let weight = self.bsdf_weight(r, specular_bounce, bsdf_pdf);
+                    radiance += throughput * self.environment.color(r.direction()) * weight;
+                    break;
+                }
+            };
+            let emitted = rec.mat_ptr.emitted(&rec);
+            if emitted.length_squared() > 0.0 {
+                let weight = self.bsdf_weight(r, specular_bounce, bsdf_pdf);
+                radiance += throughput * emitted * weight;
+            }
+
+            // the same dimensions are drawn at every bounce whatever happens,
+            // keeping well distributed samplers aligned
+            let light_choice = sampler.get_1d();
+            let light_u = sampler.get_2d();
+            let bsdf_uc = sampler.get_1d();
+            let bsdf_u = sampler.get_2d();
+            let roulette_u = sampler.get_1d();
+
+            let wo = -Vec3::unit(r.direction());
+            let sample = rec.mat_ptr.sample(&rec, wo, bsdf_uc, bsdf_u);
+            // a shadow ray adds a vertex, which must stay within the depth
+            let is_specular = matches!(&sample, Some(sample) if sample.is_specular);
+            if bounce + 1 < depth.max_depth() && !is_specular {
+                radiance += throughput
+                    * self.sample_light(world, &rec, wo, r.time(), light_choice, light_u);
+            }
+
+            let sample = match sample {
+                Some(sample) => sample,
+                None => break,
+            };
+            throughput = match depth.continue_path(
+                bounce,
+                throughput * sample.weight(rec.normal),
+                roulette_u,
+            ) {
+                Some(throughput) => throughput,
+                None => break,
+            };
+            specular_bounce = sample.is_specular;
+            bsdf_pdf = sample.pdf;
+            r = Ray::new(rec.point, sample.wi).with_time(r.time());
+        }
+        radiance
+    }
+}
+
+// Veach's power heuristic (with exponent 2) for one sample from each of two
+// strategies, weighting the one with density f_pdf
+fn power_heuristic(f_pdf: f64, g_pdf: f64) -> f64 {
+    let (f2, g2) = (f_pdf * f_pdf, g_pdf * g_pdf);
+    if f2 + g2 == 0.0 {
+        return 0.0;
+    }
+    f2 / (f2 + g2)
+}
+
+pub struct Renderer {
+    camera: Camera,
+    image_width: u32,
+    image_height: u32,
+    samples_per_pixel: u32,
+    depth: PathDepth,
+    seed: Option<u64>,
+    sampler: Box<dyn Sampler>,
+    progress: Option<Box<dyn Fn(u32) + Send + Sync>>,
+}
+
+impl Renderer {
+    pub fn new(
+        camera: Camera,
+        image_width: u32,
+        image_height: u32,
+        samples_per_pixel: u32,
+        max_depth: u32,
+    ) -> Self {
+        Self {
+            camera,
+            image_width,
+            image_height,
+            samples_per_pixel,
+            depth: PathDepth::new(max_depth)
+                .with_min_depth(3)
+                .with_russian_roulette(RussianRoulette::Throughput),
+            seed: None,
+            sampler: Box::new(IndependentSampler::new()),
+            progress: None,
+        }
+    }
+
+    // fixes the random seed, making renders bit-identical regardless of how
+    // many threads they run on; without one every render gets a fresh seed
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // how sample values are spread over each pixel; independent by default
+    pub fn with_sampler(mut self, sampler: impl Sampler + 'static) -> Self {
+        self.sampler = Box::new(sampler);
+        self
+    }
+
+    // paths always get at least min_depth bounces before Russian roulette
+    // may end them; 3 by default
+    pub fn with_min_depth(mut self, min_depth: u32) -> Self {
+        self.depth = self.depth.with_min_depth(min_depth);
+        self
+    }
+
+    // how paths past the minimum depth are ended early; by default by their
+    // throughput
+    pub fn with_russian_roulette(mut self, roulette: RussianRoulette) -> Self {
+        self.depth = self.depth.with_russian_roulette(roulette);
+        self
+    }
+
+    // called with the number of scanlines still to go each time one is
+    // done. scanlines finish on several threads at once, so it may be called
+    // concurrently; nothing is reported without one
+    pub fn with_progress(mut self, progress: impl Fn(u32) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    pub fn image_width(&self) -> u32 {
+        self.image_width
+    }
+
+    pub fn image_height(&self) -> u32 {
+        self.image_height
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    pub fn depth(&self) -> PathDepth {
+        self.depth
+    }
+
+    // scanlines are rendered in parallel, and every pixel sample's values only
+    // depend on the seed, the pixel and the sample index
+    pub fn render(&self, world: &dyn Hittable, integrator: &dyn Integrator) -> Framebuffer {
+        // allocated up front, so an image too large to hold fails before
+        // rendering rather than after
+        let mut framebuffer = Framebuffer::new(self.image_width, self.image_height);
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let remaining = AtomicU32::new(self.image_height);
+        let rows: Vec<Vec<Color>> = (0..self.image_height)
+            .into_par_iter()
+            .rev()
+            .map(|y| {
+                let row = (0..self.image_width)
+                    .map(|x| self.render_pixel(x, y, world, integrator, seed))
+                    .collect();
+                let left = remaining.fetch_sub(1, Ordering::Relaxed) - 1;
+                if let Some(progress) = &self.progress {
+                    progress(left);
+                }
+                row
+            })
+            .collect();
+
+        for (row, colors) in rows.into_iter().enumerate() {
+            for (x, color) in colors.into_iter().enumerate() {
+                framebuffer.add_samples(x as u32, row as u32, color, self.samples_per_pixel);
+            }
+        }
+        framebuffer
+    }
+
+    fn render_pixel(
+        &self,
+        x: u32,
+        y: u32,
+        world: &dyn Hittable,
+        integrator: &dyn Integrator,
+        seed: u64,
+    ) -> Color {
+        let mut sampler = self.sampler.clone_sampler();
+        let mut pixel_color = Color::zero();
+        for s in 0..self.samples_per_pixel {
+            sampler.start_pixel_sample(seed, x, y, s);
+            let (dx, dy) = sampler.get_2d();
+            let x_percent = (x as f64 + dx) / (self.image_width as f64);
+            let y_percent = (y as f64 + dy) / (self.image_height as f64);
+            let lens_sample = sampler.get_2d();
+            let r = self
+                .camera
+                .get_ray(x_percent, y_percent, lens_sample, sampler.get_1d());
+            pixel_color += integrator.ray_color(r, world, self.depth, sampler.as_mut());
+        }
+        pixel_color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{
+        Cylinder, DiffuseLight, HittableList, Lambertian, Metal, Point3, SolidColor, Sphere,
+        Transformed, Triangle, Vec3,
+    };
+
+    fn camera() -> Camera {
+        Camera::new(
+            Point3::zero(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
+            0.0,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn render_fills_framebuffer() {
+        let renderer = Renderer::new(camera(), 4, 2, 3, 5);
+        let framebuffer = renderer.render(&HittableList::default(), &PathTracer::default());
+        assert_eq!((framebuffer.width(), framebuffer.height()), (4, 2));
+        assert_eq!(framebuffer.sample_count(3, 1), 3);
+        for pixel in framebuffer.pixels() {
+            // every ray escapes to the sky, whose components all lie in [0.5, 1]
+            for i in 0..3 {
+                assert!(pixel[i] >= 0.5 && pixel[i] <= 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn progress_counts_down_the_scanlines() {
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let renderer = Renderer::new(camera(), 4, 5, 1, 2)
+            .with_progress(move |left| sink.lock().unwrap().push(left));
+        renderer.render(&HittableList::default(), &PathTracer::default());
+        let mut reported = reported.lock().unwrap().clone();
+        reported.sort_unstable();
+        assert_eq!(reported, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn seeded_render_is_deterministic_across_thread_counts() {
+        let mut world = HittableList::default();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let renderer = Renderer::new(camera(), 8, 4, 4, 10).with_seed(42);
+        let integrator = PathTracer::default();
+
+        let single_threaded = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(|| renderer.render(&world, &integrator));
+        let multi_threaded = renderer.render(&world, &integrator);
+        let single_threaded = single_threaded.pixels();
+        let multi_threaded = multi_threaded.pixels();
+        for (a, b) in single_threaded.iter().zip(multi_threaded.iter()) {
+            for i in 0..3 {
+                assert_eq!(a[i].to_bits(), b[i].to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn ray_hitting_light_sees_emission() {
+        let mut world = HittableList::default();
+        let emit = Color::new(4.0, 2.0, 1.0);
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -2.0),
+            0.5,
+            Arc::new(DiffuseLight::new(emit)),
+        )));
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        let color = PathTracer::default().ray_color(
+            r,
+            &world,
+            PathDepth::new(5),
+            &mut IndependentSampler::new(),
+        );
+        assert_eq_vec3s(color, emit);
+    }
+
+    #[test]
+    fn zero_depth_is_black() {
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 1.0, 0.0));
+        let color = PathTracer::default().ray_color(
+            r,
+            &HittableList::default(),
+            PathDepth::new(0),
+            &mut IndependentSampler::new(),
+        );
+        assert_eq_vec3s(color, Color::zero());
+    }
+
+    #[test]
+    fn escaping_ray_sees_environment() {
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 1.0, 0.0));
+        let background = Color::new(0.3, 0.7, 1.0);
+        let integrator = PathTracer::new(Arc::new(SolidColor::new(background)));
+        let color = integrator.ray_color(
+            r,
+            &HittableList::default(),
+            PathDepth::new(1),
+            &mut IndependentSampler::new(),
+        );
+        assert_eq_vec3s(color, background);
+    }
+
+    // a grey floor lit by a small spherical light straight above the origin,
+    // against a black sky
+    fn lit_floor() -> (HittableList, HittableList) {
+        let floor = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let light = Arc::new(Sphere::new(
+            Point3::new(0.0, 3.0, 0.0),
+            0.5,
+            Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        ));
+        let corners = [
+            Point3::new(-50.0, 0.0, -50.0),
+            Point3::new(50.0, 0.0, -50.0),
+            Point3::new(50.0, 0.0, 50.0),
+            Point3::new(-50.0, 0.0, 50.0),
+        ];
+        let mut world = HittableList::default();
+        world.add(Arc::new(Triangle::new(
+            corners[0],
+            corners[2],
+            corners[1],
+            floor.clone(),
+        )));
+        world.add(Arc::new(Triangle::new(
+            corners[0], corners[3], corners[2], floor,
+        )));
+        world.add(light.clone());
+        let mut lights = HittableList::default();
+        lights.add(light);
+        (world, lights)
+    }
+
+    fn estimate(
+        integrator: &dyn Integrator,
+        world: &dyn Hittable,
+        depth: PathDepth,
+        n: u32,
+    ) -> (f64, f64) {
+        let r = Ray::new(Point3::new(0.0, 1.0, 2.0), Vec3::new(0.0, -1.0, -2.0));
+        let mut sampler = IndependentSampler::new();
+        let (mut sum, mut sum2) = (0.0, 0.0);
+        for i in 0..n {
+            sampler.start_pixel_sample(7, 0, 0, i);
+            let l = integrator.ray_color(r, world, depth, &mut sampler).x();
+            sum += l;
+            sum2 += l * l;
+        }
+        let mean = sum / n as f64;
+        (mean, sum2 / n as f64 - mean * mean)
+    }
+
+    #[test]
+    fn light_sampling_agrees_with_path_tracing_with_less_noise() {
+        let (world, lights) = lit_floor();
+        let black = Arc::new(SolidColor::new(Color::zero()));
+        // a sphere of radius r and radiance E at height h subtends a cone
+        // with sin^2 = r^2 / h^2, so the floor reflects albedo * E * r^2 / h^2
+        let expected = 0.5 * 4.0 * 0.25 / 9.0;
+
+        let (path_mean, path_variance) = estimate(
+            &PathTracer::new(black.clone()),
+            &world,
+            PathDepth::new(2),
+            40_000,
+        );
+        let mis = MisPathTracer::new(black, lights).with_environment_sampling(false);
+        let (mis_mean, mis_variance) = estimate(&mis, &world, PathDepth::new(2), 40_000);
+
+        assert!((path_mean - expected).abs() < 0.1 * expected);
+        assert!((mis_mean - expected).abs() < 0.02 * expected);
+        assert!(mis_variance * 20.0 < path_variance);
+    }
+
+    #[test]
+    fn lights_that_cannot_be_sampled_are_skipped() {
+        let (mut world, mut lights) = lit_floor();
+        // an emitter without light sampling, out of sight under the floor
+        let hidden = Arc::new(
+            Transformed::new(Cylinder::new(
+                0.5,
+                1.0,
+                Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+            ))
+            .translate(Vec3::new(0.0, -5.0, 0.0)),
+        );
+        assert!(!hidden.can_sample());
+        world.add(hidden.clone());
+        lights.add(hidden);
+        assert!(!lights.can_sample());
+
+        let black = Arc::new(SolidColor::new(Color::zero()));
+        let mis = MisPathTracer::new(black, lights).with_environment_sampling(false);
+        assert_eq!(mis.lights.objects().len(), 1);
+        let expected = 0.5 * 4.0 * 0.25 / 9.0;
+        let (mis_mean, _) = estimate(&mis, &world, PathDepth::new(2), 40_000);
+        assert!((mis_mean - expected).abs() < 0.02 * expected);
+    }
+
+    #[test]
+    fn russian_roulette_keeps_estimates_unbiased() {
+        let (world, lights) = lit_floor();
+        let black = Arc::new(SolidColor::new(Color::zero()));
+        let expected = 0.5 * 4.0 * 0.25 / 9.0;
+        let depth = PathDepth::new(50)
+            .with_min_depth(1)
+            .with_russian_roulette(RussianRoulette::Throughput);
+
+        let (path_mean, _) = estimate(&PathTracer::new(black.clone()), &world, depth, 40_000);
+        let mis = MisPathTracer::new(black, lights).with_environment_sampling(false);
+        let (mis_mean, _) = estimate(&mis, &world, depth, 40_000);
+
+        assert!((path_mean - expected).abs() < 0.15 * expected);
+        assert!((mis_mean - expected).abs() < 0.03 * expected);
+    }
+
+    #[test]
+    fn deep_paths_do_not_recurse() {
+        // a ray bouncing straight between two parallel mirrors
+        let mut world = HittableList::default();
+        let mirror = Arc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0));
+        for &y in &[-1.0, 1.0] {
+            world.add(Arc::new(Sphere::new(
+                Point3::new(0.0, y * 1001.0, 0.0),
+                1000.0,
+                mirror.clone(),
+            )));
+        }
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 1.0, 0.0));
+        let color = PathTracer::default().ray_color(
+            r,
+            &world,
+            PathDepth::new(200_000),
+            &mut IndependentSampler::new(),
+        );
+        assert_eq_vec3s(color, Color::zero());
+    }
+
+    #[test]
+    fn light_seen_in_a_mirror_is_found_by_the_bsdf() {
+        let mut world = HittableList::default();
+        let mirror = Arc::new(Metal::new(Color::new(0.9, 0.9, 0.9), 0.0));
+        world.add(Arc::new(Triangle::new(
+            Point3::new(-10.0, 0.0, -10.0),
+            Point3::new(0.0, 0.0, 10.0),
+            Point3::new(10.0, 0.0, -10.0),
+            mirror,
+        )));
+        let light = Arc::new(Sphere::new(
+            Point3::new(1.5, 1.5, 0.0),
+            0.5,
+            Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        ));
+        world.add(light.clone());
+        let mut lights = HittableList::default();
+        lights.add(light);
+
+        let integrator = MisPathTracer::new(Arc::new(SolidColor::new(Color::zero())), lights);
+        let r = Ray::new(Point3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let color =
+            integrator.ray_color(r, &world, PathDepth::new(3), &mut IndependentSampler::new());
+        assert_eq_vec3s(color, Color::new(3.6, 3.6, 3.6));
+    }
+
+    #[test]
+    fn power_heuristic_favours_the_denser_strategy() {
+        assert_f64_eq(power_heuristic(1.0, 1.0), 0.5);
+        assert_f64_eq(power_heuristic(3.0, 1.0), 0.9);
+        assert_f64_eq(power_heuristic(3.0, 1.0) + power_heuristic(1.0, 3.0), 1.0);
+        assert_f64_eq(power_heuristic(0.0, 0.0), 0.0);
+    }
+}
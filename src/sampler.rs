@@ -0,0 +1,488 @@
+use crate::{rng::hash, Pcg32};
+
+// supplies the sample values for one pixel sample at a time. every request
+// (the position in the pixel, the lens position, the choices made at each
+// bounce) uses up the next dimension, so that well distributed samplers can
+// spread the values of each dimension evenly across a pixel's samples
+pub trait Sampler: Send + Sync {
+    // starts sample `index` of pixel (x, y), going back to the first dimension
+    fn start_pixel_sample(&mut self, seed: u64, x: u32, y: u32, index: u32);
+
+    // a uniform value in [0, 1)
+    fn get_1d(&mut self) -> f64;
+
+    // a uniform point in [0, 1)^2
+    fn get_2d(&mut self) -> (f64, f64);
+
+    // a sampler of the same kind, for rendering another pixel
+    fn clone_sampler(&self) -> Box<dyn Sampler>;
+}
+
+// largest f64 below 1, so that no sample ever reaches the end of [0, 1)
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// plain uniform random numbers, as the book uses
+pub struct IndependentSampler {
+    rng: Pcg32,
+}
+
+impl IndependentSampler {
+    pub fn new() -> Self {
+        Self { rng: Pcg32::new(0) }
+    }
+}
+
+impl Default for IndependentSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, seed: u64, x: u32, y: u32, index: u32) {
+        self.rng = Pcg32::for_sample(seed, x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.rand_f64()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.rand_f64(), self.rng.rand_f64())
+    }
+
+    fn clone_sampler(&self) -> Box<dyn Sampler> {
+        Box::new(Self::new())
+    }
+}
+
+// where we are in a pixel's samples, shared by the well distributed samplers
+#[derive(Clone, Default)]
+struct PixelSample {
+    pixel_hash: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl PixelSample {
+    fn start(&mut self, seed: u64, x: u32, y: u32, index: u32) {
+        self.pixel_hash = hash(seed, (y as u64) << 32 | x as u64);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    // a hash unique to this pixel and the next dimension
+    fn next_dimension(&mut self) -> u64 {
+        self.dimension += 1;
+        hash(self.pixel_hash, self.dimension)
+    }
+}
+
+// jittered grid: each dimension is split into one stratum per sample (a grid
+// of about sqrt(n) by sqrt(n) cells in 2D), and every sample of a pixel gets
+// its own stratum with a random position inside it
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    x_strata: u32,
+    y_strata: u32,
+    state: PixelSample,
+    rng: Pcg32,
+}
+
+impl StratifiedSampler {
+    // stratifies best when the renderer takes exactly this many samples
+    pub fn new(samples_per_pixel: u32) -> Self {
+        assert!(samples_per_pixel > 0, "sampler needs at least one sample");
+        let x_strata = (samples_per_pixel as f64).sqrt().ceil() as u32;
+        let y_strata = samples_per_pixel.div_ceil(x_strata);
+        Self {
+            samples_per_pixel,
+            x_strata,
+            y_strata,
+            state: PixelSample::default(),
+            rng: Pcg32::new(0),
+        }
+    }
+
+    // the stratum of the current sample, shuffled differently per dimension
+    fn stratum(&mut self, count: u32) -> u32 {
+        let permutation = self.state.next_dimension() as u32;
+        permutation_element(self.state.index % count, count, permutation)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, seed: u64, x: u32, y: u32, index: u32) {
+        self.state.start(seed, x, y, index);
+        self.rng = Pcg32::for_sample(seed, x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let stratum = self.stratum(self.samples_per_pixel);
+        let jitter = self.rng.rand_f64();
+        ((stratum as f64 + jitter) / self.samples_per_pixel as f64).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let stratum = self.stratum(self.x_strata * self.y_strata);
+        let (sx, sy) = (stratum % self.x_strata, stratum / self.x_strata);
+        let (jx, jy) = (self.rng.rand_f64(), self.rng.rand_f64());
+        (
+            ((sx as f64 + jx) / self.x_strata as f64).min(ONE_MINUS_EPSILON),
+            ((sy as f64 + jy) / self.y_strata as f64).min(ONE_MINUS_EPSILON),
+        )
+    }
+
+    fn clone_sampler(&self) -> Box<dyn Sampler> {
+        Box::new(Self::new(self.samples_per_pixel))
+    }
+}
+
+// Kensler's hashed permutation: element i of a random permutation of
+// 0..count chosen by `seed`, without storing the permutation
+fn permutation_element(mut i: u32, count: u32, seed: u32) -> u32 {
+    let mut w = count - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        // cycle walk until we land inside 0..count
+        if i < count {
+            break;
+        }
+    }
+    ((i as u64 + seed as u64) % count as u64) as u32
+}
+
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+// Halton sequence: the radical inverse of the sample index in a different
+// prime base for every dimension, randomly shifted per pixel (Cranley-Patterson
+// rotation) so neighbouring pixels don't share the same pattern. dimensions
+// past the prime table fall back to independent random numbers
+pub struct HaltonSampler {
+    state: PixelSample,
+    rng: Pcg32,
+}
+
+impl HaltonSampler {
+    pub fn new() -> Self {
+        Self {
+            state: PixelSample::default(),
+            rng: Pcg32::new(0),
+        }
+    }
+}
+
+impl Default for HaltonSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, seed: u64, x: u32, y: u32, index: u32) {
+        self.state.start(seed, x, y, index);
+        self.rng = Pcg32::for_sample(seed, x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let base = match PRIMES.get(self.state.dimension as usize) {
+            Some(&base) => base,
+            None => return self.rng.rand_f64(),
+        };
+        let offset = Pcg32::new(self.state.next_dimension()).rand_f64();
+        let value = radical_inverse(base, self.state.index as u64) + offset;
+        (if value >= 1.0 { value - 1.0 } else { value }).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+
+    fn clone_sampler(&self) -> Box<dyn Sampler> {
+        Box::new(Self::new())
+    }
+}
+
+// mirrors the base `base` digits of a around the radix point
+fn radical_inverse(base: u64, mut a: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut reversed = 0;
+    let mut inverse_base_n = 1.0;
+    while a > 0 {
+        let next = a / base;
+        let digit = a - next * base;
+        reversed = reversed * base + digit;
+        inverse_base_n *= inverse_base;
+        a = next;
+    }
+    (reversed as f64 * inverse_base_n).min(ONE_MINUS_EPSILON)
+}
+
+// the first two dimensions of the Sobol' sequence with hash-based Owen
+// scrambling, after Burley's "Practical Hash-based Owen Scrambling". every
+// request shuffles the sample order and scrambles the values with hashes of
+// its own, so any number of dimensions stay well distributed (as pairs) and
+// unrelated to each other
+pub struct SobolSampler {
+    state: PixelSample,
+}
+
+impl SobolSampler {
+    pub fn new() -> Self {
+        Self {
+            state: PixelSample::default(),
+        }
+    }
+
+    // the next dimension's seeds for shuffling the sample order and for
+    // scrambling its first and second coordinates, each hashed from the
+    // dimension on its own so that no two share any bits
+    fn next_dimension(&mut self) -> DimensionSeeds {
+        let dimension = self.state.next_dimension();
+        DimensionSeeds {
+            shuffle: hash(dimension, 0) as u32,
+            x: hash(dimension, 1) as u32,
+            y: hash(dimension, 2) as u32,
+        }
+    }
+
+    // the order this dimension visits the sequence's points in
+    fn shuffled_index(&self, seeds: &DimensionSeeds) -> u32 {
+        nested_uniform_scramble(self.state.index, seeds.shuffle)
+    }
+}
+
+struct DimensionSeeds {
+    shuffle: u32,
+    x: u32,
+    y: u32,
+}
+
+impl Default for SobolSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, seed: u64, x: u32, y: u32, index: u32) {
+        self.state.start(seed, x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let seeds = self.next_dimension();
+        let index = self.shuffled_index(&seeds);
+        let x = nested_uniform_scramble(index.reverse_bits(), seeds.x);
+        to_unit_interval(x)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let seeds = self.next_dimension();
+        let index = self.shuffled_index(&seeds);
+        let x = nested_uniform_scramble(index.reverse_bits(), seeds.x);
+        let y = nested_uniform_scramble(sobol_second_dimension(index), seeds.y);
+        (to_unit_interval(x), to_unit_interval(y))
+    }
+
+    fn clone_sampler(&self) -> Box<dyn Sampler> {
+        Box::new(Self::new())
+    }
+}
+
+// the second dimension's generator matrix is Pascal's triangle mod 2
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut x = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            x ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    x
+}
+
+// Laine and Karras' hash, which only lets each bit depend on lower bits
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+// Owen scrambling of a fixed point value in [0, 1): each bit is flipped
+// depending on the bits above it. also used to shuffle sample indices
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn to_unit_interval(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samplers(samples_per_pixel: u32) -> Vec<Box<dyn Sampler>> {
+        vec![
+            Box::new(IndependentSampler::new()),
+            Box::new(StratifiedSampler::new(samples_per_pixel)),
+            Box::new(HaltonSampler::new()),
+            Box::new(SobolSampler::new()),
+        ]
+    }
+
+    #[test]
+    fn permutation_is_a_permutation() {
+        for &count in &[1, 5, 16, 500] {
+            let mut seen: Vec<u32> = (0..count)
+                .map(|i| permutation_element(i, count, 12345))
+                .collect();
+            seen.sort_unstable();
+            assert_eq!(seen, (0..count).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn radical_inverse_mirrors_digits() {
+        assert_eq!(radical_inverse(2, 1), 0.5);
+        assert_eq!(radical_inverse(2, 6), 0.375);
+        assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-15);
+    }
+
+    #[test]
+    fn sobol_points_fill_elementary_intervals() {
+        // the first 16 points of a scrambled (0, 2)-sequence put exactly one
+        // point in each cell of a 4x4 grid
+        let mut sampler = SobolSampler::new();
+        let mut cells = [0; 16];
+        for index in 0..16 {
+            sampler.start_pixel_sample(3, 1, 2, index);
+            let (u, v) = sampler.get_2d();
+            cells[(u * 4.0) as usize + 4 * (v * 4.0) as usize] += 1;
+        }
+        assert_eq!(cells, [1; 16]);
+    }
+
+    #[test]
+    fn sobol_scrambling_seeds_are_unrelated() {
+        // every bit of one seed agrees with every bit of another about half
+        // the time, over many pixels and dimensions
+        let mut sampler = SobolSampler::new();
+        let mut seeds = Vec::new();
+        for pixel in 0..256 {
+            sampler.start_pixel_sample(7, pixel, 0, 0);
+            for _ in 0..16 {
+                let s = sampler.next_dimension();
+                seeds.push([(s.shuffle, s.x), (s.shuffle, s.y), (s.x, s.y)]);
+            }
+        }
+        for pair in 0..3 {
+            for i in 0..32 {
+                for j in 0..32 {
+                    let agreeing = seeds
+                        .iter()
+                        .filter(|pairs| {
+                            let (a, b) = pairs[pair];
+                            (a >> i & 1) == (b >> j & 1)
+                        })
+                        .count();
+                    let fraction = agreeing as f64 / seeds.len() as f64;
+                    assert!((fraction - 0.5).abs() < 0.05, "bits {} and {}", i, j);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_points_fill_strata() {
+        let mut sampler = StratifiedSampler::new(16);
+        let mut cells = [0; 16];
+        let mut strata_1d = [0; 16];
+        for index in 0..16 {
+            sampler.start_pixel_sample(3, 1, 2, index);
+            let (u, v) = sampler.get_2d();
+            cells[(u * 4.0) as usize + 4 * (v * 4.0) as usize] += 1;
+            strata_1d[(sampler.get_1d() * 16.0) as usize] += 1;
+        }
+        assert_eq!(cells, [1; 16]);
+        assert_eq!(strata_1d, [1; 16]);
+    }
+
+    #[test]
+    fn samples_are_reproducible_and_in_range() {
+        for mut sampler in samplers(8) {
+            let mut copy = sampler.clone_sampler();
+            for index in 0..8 {
+                sampler.start_pixel_sample(9, 4, 5, index);
+                copy.start_pixel_sample(9, 4, 5, index);
+                // enough dimensions to run past the Halton prime table
+                for _ in 0..40 {
+                    let (u, v) = sampler.get_2d();
+                    assert_eq!((u, v), copy.get_2d());
+                    assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+                    let w = sampler.get_1d();
+                    assert_eq!(w, copy.get_1d());
+                    assert!((0.0..1.0).contains(&w));
+                }
+            }
+        }
+    }
+
+    // mean squared error of estimating the integral of a smooth function
+    // over the unit square with 16 samples, averaged over many pixels
+    fn integration_error(sampler: &mut dyn Sampler) -> f64 {
+        let f = |u: f64, v: f64| (u * 3.0).sin() * (v * 2.0).cos() + u * v;
+        let expected = (1.0 - 3_f64.cos()) / 3.0 * 2_f64.sin() / 2.0 + 0.25;
+        let mut squared_error = 0.0;
+        for pixel in 0..256 {
+            let mut sum = 0.0;
+            for index in 0..16 {
+                sampler.start_pixel_sample(1, pixel, 0, index);
+                // skip a dimension, like the pixel position before the lens
+                sampler.get_2d();
+                let (u, v) = sampler.get_2d();
+                sum += f(u, v);
+            }
+            squared_error += (sum / 16.0 - expected).powi(2);
+        }
+        squared_error / 256.0
+    }
+
+    #[test]
+    fn well_distributed_samplers_beat_independent_samples() {
+        let independent = integration_error(&mut IndependentSampler::new());
+        for mut sampler in samplers(16).into_iter().skip(1) {
+            assert!(integration_error(sampler.as_mut()) < independent / 4.0);
+        }
+    }
+}
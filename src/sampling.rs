@@ -0,0 +1,141 @@
+use crate::Vec3;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+// deterministic warps from uniform samples in [0, 1)^n to other shapes, so
+// that well distributed samples stay well distributed after mapping
+
+// uniformly distributed unit vector, with density 1/4pi
+pub fn sample_uniform_sphere(u: (f64, f64)) -> Vec3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// uniformly distributed point inside the unit ball
+pub fn sample_unit_ball(u: (f64, f64), radius_u: f64) -> Vec3 {
+    radius_u.cbrt() * sample_uniform_sphere(u)
+}
+
+// Shirley and Chiu's concentric mapping onto the unit disk, which keeps
+// strata compact (unlike the naive polar mapping)
+pub fn sample_concentric_disk(u: (f64, f64)) -> (f64, f64) {
+    let (x, y) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+// cosine weighted direction around +z (Malley's method), with density
+// cos(theta) / pi
+pub fn sample_cosine_hemisphere(u: (f64, f64)) -> Vec3 {
+    let (x, y) = sample_concentric_disk(u);
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+    Vec3::new(x, y, z)
+}
+
+// orthonormal basis around a unit vector w, for turning directions sampled
+// around +z into world space
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    // Duff et al.'s branchless construction from "Building an Orthonormal
+    // Basis, Revisited"
+    pub fn from_w(w: Vec3) -> Self {
+        let sign = 1_f64.copysign(w.z());
+        let a = -1.0 / (sign + w.z());
+        let b = w.x() * w.y() * a;
+        Self {
+            u: Vec3::new(1.0 + sign * w.x() * w.x() * a, sign * b, -sign * w.x()),
+            v: Vec3::new(b, sign + w.y() * w.y() * a, -w.y()),
+            w,
+        }
+    }
+
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn sphere_samples_are_unit_and_balanced() {
+        let mut sum = Vec3::zero();
+        for u in grid(32) {
+            let d = sample_uniform_sphere(u);
+            assert_f64_eq(d.length(), 1.0);
+            sum += d;
+        }
+        assert!(sum.length() / (32.0 * 32.0) < 1e-3);
+    }
+
+    #[test]
+    fn disk_samples_cover_the_disk_uniformly() {
+        let mut inner = 0;
+        for u in grid(64) {
+            let (x, y) = sample_concentric_disk(u);
+            let r2 = x * x + y * y;
+            assert!(r2 <= 1.0 + 1e-12);
+            if r2 < 0.25 {
+                inner += 1;
+            }
+        }
+        // the disk of radius 1/2 has a quarter of the area
+        assert!((inner as f64 / (64.0 * 64.0) - 0.25).abs() < 0.01);
+        assert_eq!(sample_concentric_disk((0.5, 0.5)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn cosine_samples_lean_towards_z() {
+        let mut mean_z = 0.0;
+        for u in grid(32) {
+            let d = sample_cosine_hemisphere(u);
+            assert_f64_eq(d.length(), 1.0);
+            assert!(d.z() >= 0.0);
+            mean_z += d.z();
+        }
+        // E[cos] under a cos/pi density is 2/3
+        assert!((mean_z / (32.0 * 32.0) - 2.0 / 3.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn onb_is_orthonormal() {
+        for &w in &[
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::unit(Vec3::new(1.0, -2.0, 0.5)),
+        ] {
+            let onb = Onb::from_w(w);
+            let (u, v) = (
+                onb.local(Vec3::new(1.0, 0.0, 0.0)),
+                onb.local(Vec3::new(0.0, 1.0, 0.0)),
+            );
+            assert_f64_eq(u.length(), 1.0);
+            assert_f64_eq(v.length(), 1.0);
+            assert_f64_eq(Vec3::dot(u, v), 0.0);
+            assert_f64_eq(Vec3::dot(u, w), 0.0);
+            assert_eq_vec3s(onb.local(Vec3::new(0.0, 0.0, 1.0)), w);
+        }
+    }
+
+    #[test]
+    fn ball_samples_stay_inside() {
+        for u in grid(16) {
+            assert!(sample_unit_ball(u, 0.999).length() < 1.0);
+        }
+    }
+}
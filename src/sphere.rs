@@ -1,50 +1,288 @@
-use crate::{HitRecord, Hittable, Material, Point3, Ray, Vec3};
-use std::rc::Rc;
+use crate::{
+    clamp, sample_uniform_sphere, Aabb, HitRecord, Hittable, Material, Onb, Point3, Ray, Vec3,
+};
+use std::{f64::consts::PI, sync::Arc};
+
 pub struct Sphere {
     center: Point3,
     radius: f64,
-    mat_ptr: Rc<dyn Material>,
+    mat_ptr: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Point3, radius: f64, mat_ptr: Rc<dyn Material>) -> Self {
+    pub fn new(center: Point3, radius: f64, mat_ptr: Arc<dyn Material>) -> Self {
         Self {
             center,
             radius,
             mat_ptr,
         }
     }
+
+    // texture coordinates of a point on the unit sphere: u goes once around
+    // the y axis starting from -x, v from the bottom (-y) to the top (+y)
+    fn uv(p: Point3) -> (f64, f64) {
+        let theta = clamp(-p.y(), -1.0, 1.0).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    // 1 - cos theta_max of the cone the sphere subtends from origin, or None
+    // if origin is inside it. computed without subtracting from 1, which
+    // would lose small distant lights to cancellation
+    fn cone(&self, origin: Point3) -> Option<f64> {
+        let distance_squared = (self.center - origin).length_squared();
+        let sin_squared = self.radius * self.radius / distance_squared;
+        if sin_squared >= 1.0 {
+            return None;
+        }
+        let cos_theta_max = (1.0 - sin_squared).sqrt();
+        Some(sin_squared / (1.0 + cos_theta_max))
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = r.origin() - self.center;
-        let a = r.direction().length_squared();
-        let half_b = Vec3::dot(r.direction(), oc);
-        let c = oc.length_squared() - (self.radius * self.radius);
-        let discriminant = (half_b * half_b) - (a * c);
-        if discriminant < 0.0 {
-            return None;
+        hit_sphere(self.center, self.radius, &self.mat_ptr, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(sphere_box(self.center, self.radius))
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    // seen from outside, directions are sampled uniformly within the cone
+    // the sphere subtends; from inside, over the whole sphere of directions
+    fn pdf_value(&self, origin: Point3, direction: Vec3, _time: f64) -> f64 {
+        if self
+            .hit(Ray::new(origin, direction), 0.001, f64::INFINITY)
+            .is_none()
+        {
+            return 0.0;
         }
-        let sqrt_d = discriminant.sqrt();
-        // Find the nearest root that lies in the acceptable range
-        let mut root = (-half_b - sqrt_d) / a;
-        if root < t_min || root > t_max {
-            root = (-half_b + sqrt_d) / a;
-            if root < t_min || root > t_max {
-                return None;
+        match self.cone(origin) {
+            Some(one_minus_cos_theta_max) => 1.0 / (2.0 * PI * one_minus_cos_theta_max),
+            None => 1.0 / (4.0 * PI),
+        }
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), _time: f64) -> Vec3 {
+        match self.cone(origin) {
+            Some(one_minus_cos_theta_max) => {
+                let z = 1.0 - u.1 * one_minus_cos_theta_max;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * u.0;
+                let towards = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+                Onb::from_w(Vec3::unit(self.center - origin)).local(towards)
             }
+            None => sample_uniform_sphere(u),
         }
+    }
+}
 
-        let mut hit_rec = HitRecord {
-            t: root,
-            point: r.at(root),
-            normal: (r.at(root) - self.center) / self.radius, // unit surface normal
-            is_front_face: true,
-            mat_ptr: self.mat_ptr.clone(),
-        };
-        hit_rec.set_face_normal(r, hit_rec.normal);
+// sphere moving in a straight line from center0 at time0 to center1 at
+// time1, for motion blur. it rests at either end outside that interval, so
+// its bounding box covers every time a ray may have
+pub struct MovingSphere {
+    center0: Point3,
+    center1: Point3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    mat_ptr: Arc<dyn Material>,
+}
 
-        Some(hit_rec)
+impl MovingSphere {
+    pub fn new(
+        (center0, time0): (Point3, f64),
+        (center1, time1): (Point3, f64),
+        radius: f64,
+        mat_ptr: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            mat_ptr,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Point3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+        let t = clamp((time - self.time0) / (self.time1 - self.time0), 0.0, 1.0);
+        self.center0 + t * (self.center1 - self.center0)
+    }
+
+    // the sphere as it is at that time
+    fn at(&self, time: f64) -> Sphere {
+        Sphere::new(self.center(time), self.radius, self.mat_ptr.clone())
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let center = self.center(r.time());
+        let mut rec = hit_sphere(center, self.radius, &self.mat_ptr, r, t_min, t_max)?;
+        // solid textures move with the sphere, so they see the hit where it
+        // would be on the sphere at its starting position
+        rec.local_point = rec.point - (center - self.center0);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::surrounding_box(
+            sphere_box(self.center0, self.radius),
+            sphere_box(self.center1, self.radius),
+        ))
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    // sampled like a Sphere, where it is at the time of the shadow ray
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        self.at(time).pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), time: f64) -> Vec3 {
+        self.at(time).random(origin, u, time)
+    }
+}
+
+fn hit_sphere(
+    center: Point3,
+    radius: f64,
+    mat_ptr: &Arc<dyn Material>,
+    r: Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord> {
+    let oc = r.origin() - center;
+    let a = r.direction().length_squared();
+    let half_b = Vec3::dot(r.direction(), oc);
+    let c = oc.length_squared() - (radius * radius);
+    let discriminant = (half_b * half_b) - (a * c);
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrt_d = discriminant.sqrt();
+    // Find the nearest root that lies in the acceptable range
+    let mut root = (-half_b - sqrt_d) / a;
+    if root < t_min || root > t_max {
+        root = (-half_b + sqrt_d) / a;
+        if root < t_min || root > t_max {
+            return None;
+        }
+    }
+
+    let point = r.at(root);
+    let outward_normal = (point - center) / radius; // unit surface normal
+    let (u, v) = Sphere::uv(outward_normal);
+    let mut hit_rec = HitRecord {
+        t: root,
+        point,
+        local_point: point,
+        normal: outward_normal,
+        u,
+        v,
+        is_front_face: true,
+        mat_ptr: mat_ptr.clone(),
+    };
+    hit_rec.set_face_normal(r, hit_rec.normal);
+
+    Some(hit_rec)
+}
+
+fn sphere_box(center: Point3, radius: f64) -> Aabb {
+    // abs, since hollow spheres are modelled with a negative radius
+    let radius = Vec3::new(radius.abs(), radius.abs(), radius.abs());
+    Aabb::new(center - radius, center + radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{Color, Lambertian};
+
+    #[test]
+    fn uvs_wrap_around_the_y_axis() {
+        let cases = [
+            (Vec3::new(1.0, 0.0, 0.0), (0.5, 0.5)),
+            (Vec3::new(0.0, 1.0, 0.0), (0.5, 1.0)),
+            (Vec3::new(0.0, -1.0, 0.0), (0.5, 0.0)),
+            (Vec3::new(-1.0, 0.0, 0.0), (0.0, 0.5)),
+            (Vec3::new(0.0, 0.0, 1.0), (0.25, 0.5)),
+            (Vec3::new(0.0, 0.0, -1.0), (0.75, 0.5)),
+        ];
+        for &(p, (u, v)) in &cases {
+            let uv = Sphere::uv(p);
+            assert_f64_eq(uv.0, u);
+            assert_f64_eq(uv.1, v);
+        }
+    }
+
+    #[test]
+    fn hit_records_carry_uvs() {
+        let material = Arc::new(Lambertian::new(Color::zero()));
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, -3.0), 2.0, material);
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        let rec = sphere.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_f64_eq(rec.u, 0.25);
+        assert_f64_eq(rec.v, 0.5);
+    }
+
+    #[test]
+    fn moving_sphere_follows_ray_time() {
+        let material = Arc::new(Lambertian::new(Color::zero()));
+        let sphere = MovingSphere::new(
+            (Point3::new(0.0, 0.0, -3.0), 0.0),
+            (Point3::new(2.0, 0.0, -3.0), 1.0),
+            0.5,
+            material,
+        );
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        let rec = sphere.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_f64_eq(rec.t, 2.5);
+        assert!(sphere.hit(r.with_time(1.0), 0.001, f64::INFINITY).is_none());
+        // halfway through, the sphere has moved one unit along x
+        let r = Ray::new(Point3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(sphere.hit(r.with_time(0.5), 0.001, f64::INFINITY).is_some());
+        // and it rests outside its time interval
+        assert_eq_vec3s(sphere.center(2.0), Point3::new(2.0, 0.0, -3.0));
+        assert_eq_vec3s(sphere.center(-1.0), Point3::new(0.0, 0.0, -3.0));
+
+        let bbox = sphere.bounding_box().unwrap();
+        assert_eq_vec3s(bbox.min(), Point3::new(-0.5, -0.5, -3.5));
+        assert_eq_vec3s(bbox.max(), Point3::new(2.5, 0.5, -2.5));
+    }
+
+    #[test]
+    fn moving_sphere_light_is_sampled_where_it_is() {
+        let material = Arc::new(Lambertian::new(Color::zero()));
+        let sphere = MovingSphere::new(
+            (Point3::new(0.0, 0.0, -3.0), 0.0),
+            (Point3::new(2.0, 0.0, -3.0), 1.0),
+            0.5,
+            material,
+        );
+        let origin = Point3::zero();
+        for &time in &[0.0, 0.5, 1.0] {
+            let direction = sphere.random(origin, (0.3, 0.6), time);
+            let r = Ray::new(origin, direction).with_time(time);
+            assert!(sphere.hit(r, 0.001, f64::INFINITY).is_some());
+            assert!(sphere.pdf_value(origin, direction, time) > 0.0);
+        }
+        // where it was at the start, it no longer is at the end
+        let towards_start = Vec3::new(0.0, 0.0, -1.0);
+        assert!(sphere.pdf_value(origin, towards_start, 0.0) > 0.0);
+        assert_eq!(sphere.pdf_value(origin, towards_start, 1.0), 0.0);
     }
 }
@@ -0,0 +1,414 @@
+use crate::{srgb_to_linear, Color, HdrError, HdrImage, Point3};
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+    sync::Arc,
+};
+
+// a color that varies over a surface, looked up from the hit's surface
+// coordinates (u, v) and/or its position p in object space (the hit
+// record's local_point), so it moves along with transformed objects
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color;
+}
+
+pub struct ConstantTexture {
+    color: Color,
+}
+
+impl ConstantTexture {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for ConstantTexture {
+    fn value(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        self.color
+    }
+}
+
+// solid checkerboard of cubes with sides `scale`, alternating between two
+// textures. it depends on the position only, so it needs no uvs
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+        Self::new(
+            scale,
+            Arc::new(ConstantTexture::new(even)),
+            Arc::new(ConstantTexture::new(odd)),
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        let cell = (self.inv_scale * p.x()).floor()
+            + (self.inv_scale * p.y()).floor()
+            + (self.inv_scale * p.z()).floor();
+        if cell.rem_euclid(2.0) == 0.0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io(io::Error),
+    Png(png::DecodingError),
+    Hdr(HdrError),
+    UnsupportedFormat(String),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::Io(err) => write!(f, "{}", err),
+            TextureError::Png(err) => write!(f, "png decoding failed: {}", err),
+            TextureError::Hdr(err) => write!(f, "{}", err),
+            TextureError::UnsupportedFormat(ext) => {
+                write!(f, "unsupported image format '{}'", ext)
+            }
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TextureError::Io(err) => Some(err),
+            TextureError::Png(err) => Some(err),
+            TextureError::Hdr(err) => Some(err),
+            TextureError::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for TextureError {
+    fn from(err: io::Error) -> Self {
+        TextureError::Io(err)
+    }
+}
+
+impl From<png::DecodingError> for TextureError {
+    fn from(err: png::DecodingError) -> Self {
+        TextureError::Png(err)
+    }
+}
+
+impl From<HdrError> for TextureError {
+    fn from(err: HdrError) -> Self {
+        TextureError::Hdr(err)
+    }
+}
+
+// what an image texture does with uvs outside [0, 1]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    // the texel index actually read for index i along an axis of n texels
+    fn wrap(self, i: i64, n: i64) -> usize {
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.max(0).min(n - 1),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n {
+                    i
+                } else {
+                    2 * n - 1 - i
+                }
+            }
+        };
+        i as usize
+    }
+
+    // brings a texel coordinate along an axis of n texels into a small range
+    // reading the same texels, so that huge or infinite uvs (as on a Plane)
+    // still give valid indices. infinite and NaN coordinates have no place
+    // to repeat from, and read the start of the image
+    fn reduce(self, x: f64, n: f64) -> f64 {
+        match self {
+            WrapMode::Clamp if !x.is_nan() => x.max(-1.0).min(n),
+            _ if x.is_finite() => x.rem_euclid(2.0 * n),
+            _ => 0.0,
+        }
+    }
+}
+
+// bilinearly filtered image, with v = 0 at the bottom row and texel centres
+// at half-integer multiples of the texel size
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>, // linear, row by row from the top
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "a texture needs pixels");
+        assert_eq!(pixels.len(), width * height, "wrong number of pixels");
+        Self {
+            width,
+            height,
+            pixels,
+            wrap: WrapMode::Repeat,
+        }
+    }
+
+    // reads an image by file extension: .png is taken to be sRGB encoded,
+    // .hdr and .pfm to be linear already
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TextureError> {
+        Self::load_with(path.as_ref(), true)
+    }
+
+    // like load, but for pngs holding data rather than colors, such as
+    // roughness maps, which aren't sRGB decoded
+    pub fn load_linear(path: impl AsRef<Path>) -> Result<Self, TextureError> {
+        Self::load_with(path.as_ref(), false)
+    }
+
+    fn load_with(path: &Path, srgb: bool) -> Result<Self, TextureError> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Self::read_png(BufReader::new(File::open(path)?), srgb),
+            "hdr" | "pfm" => {
+                let image = HdrImage::load(path)?;
+                Ok(Self::new(image.width, image.height, image.pixels))
+            }
+            _ => Err(TextureError::UnsupportedFormat(extension)),
+        }
+    }
+
+    // 8 or 16 bit greyscale or RGB png (palettes and smaller depths are
+    // expanded); alpha is ignored
+    pub fn read_png<R: Read>(reader: R, srgb: bool) -> Result<Self, TextureError> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let channels = info.color_type.samples();
+        let (bytes_per_sample, max) = match info.bit_depth {
+            png::BitDepth::Sixteen => (2, 65535.0),
+            _ => (1, 255.0),
+        };
+        let decode = |bytes: &[u8]| {
+            let sample = if bytes_per_sample == 2 {
+                u16::from_be_bytes([bytes[0], bytes[1]]) as f64
+            } else {
+                bytes[0] as f64
+            };
+            let encoded = sample / max;
+            if srgb {
+                srgb_to_linear(encoded)
+            } else {
+                encoded
+            }
+        };
+        let pixels = buffer[..info.buffer_size()]
+            .chunks_exact(channels * bytes_per_sample)
+            .map(|pixel| {
+                let sample = |c: usize| decode(&pixel[c * bytes_per_sample..]);
+                if channels < 3 {
+                    let grey = sample(0);
+                    Color::new(grey, grey, grey)
+                } else {
+                    Color::new(sample(0), sample(1), sample(2))
+                }
+            })
+            .collect();
+        Ok(Self::new(info.width as usize, info.height as usize, pixels))
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = self.wrap.wrap(x, self.width as i64);
+        let y = self.wrap.wrap(y, self.height as i64);
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point3) -> Color {
+        let (width, height) = (self.width as f64, self.height as f64);
+        let x = self.wrap.reduce(u * width - 0.5, width);
+        let y = self.wrap.reduce((1.0 - v) * height - 0.5, height);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = (1.0 - fx) * self.texel(x0, y0) + fx * self.texel(x0 + 1, y0);
+        let bottom = (1.0 - fx) * self.texel(x0, y0 + 1) + fx * self.texel(x0 + 1, y0 + 1);
+        (1.0 - fy) * top + fy * bottom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    // 2x2 image: black and red on top, green and blue below
+    fn image() -> ImageTexture {
+        ImageTexture::new(
+            2,
+            2,
+            vec![
+                Color::zero(),
+                Color::new(1.0, 0.0, 0.0),
+                Color::new(0.0, 1.0, 0.0),
+                Color::new(0.0, 0.0, 1.0),
+            ],
+        )
+    }
+
+    #[test]
+    fn checker_alternates_in_3d() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let checker = CheckerTexture::from_colors(0.5, white, Color::zero());
+        assert_eq_vec3s(checker.value(0.0, 0.0, Point3::new(0.1, 0.1, 0.1)), white);
+        assert_eq_vec3s(
+            checker.value(0.0, 0.0, Point3::new(0.6, 0.1, 0.1)),
+            Color::zero(),
+        );
+        assert_eq_vec3s(checker.value(0.0, 0.0, Point3::new(0.6, 0.6, 0.1)), white);
+        assert_eq_vec3s(
+            checker.value(0.0, 0.0, Point3::new(-0.1, 0.1, 0.1)),
+            Color::zero(),
+        );
+    }
+
+    #[test]
+    fn texel_centres_are_exact() {
+        let texture = image();
+        let p = Point3::zero();
+        assert_eq_vec3s(texture.value(0.25, 0.75, p), Color::zero());
+        assert_eq_vec3s(texture.value(0.75, 0.75, p), Color::new(1.0, 0.0, 0.0));
+        assert_eq_vec3s(texture.value(0.25, 0.25, p), Color::new(0.0, 1.0, 0.0));
+        assert_eq_vec3s(texture.value(0.75, 0.25, p), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn bilinear_filtering_blends_neighbours() {
+        let texture = image();
+        assert_eq_vec3s(
+            texture.value(0.5, 0.5, Point3::zero()),
+            Color::new(0.25, 0.25, 0.25),
+        );
+        assert_eq_vec3s(
+            texture.value(0.5, 0.75, Point3::zero()),
+            Color::new(0.5, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn huge_and_infinite_uvs_stay_in_the_image() {
+        for &wrap in &[WrapMode::Repeat, WrapMode::Clamp, WrapMode::Mirror] {
+            let texture = image().with_wrap(wrap);
+            for &u in &[f64::INFINITY, f64::NEG_INFINITY, 1e300, -1e300, f64::NAN] {
+                let color = texture.value(u, 0.5, Point3::zero());
+                let color = texture.value(0.25, u, Point3::zero()) + color;
+                for i in 0..3 {
+                    assert!(color[i].is_finite());
+                }
+            }
+        }
+        // clamping reads the edge, and far uvs repeat like near ones
+        let clamped = image().with_wrap(WrapMode::Clamp);
+        assert_eq_vec3s(
+            clamped.value(f64::INFINITY, 1.0, Point3::zero()),
+            Color::new(1.0, 0.0, 0.0),
+        );
+        let repeated = image();
+        assert_eq_vec3s(
+            repeated.value(1e6 + 0.25, 0.75, Point3::zero()),
+            repeated.value(0.25, 0.75, Point3::zero()),
+        );
+    }
+
+    #[test]
+    fn wrap_modes() {
+        assert_eq!(WrapMode::Repeat.wrap(-1, 4), 3);
+        assert_eq!(WrapMode::Repeat.wrap(5, 4), 1);
+        assert_eq!(WrapMode::Clamp.wrap(-1, 4), 0);
+        assert_eq!(WrapMode::Clamp.wrap(5, 4), 3);
+        assert_eq!(WrapMode::Mirror.wrap(-1, 4), 0);
+        assert_eq!(WrapMode::Mirror.wrap(5, 4), 2);
+
+        // on the left edge repeat blends in the right column, clamp doesn't
+        let p = Point3::zero();
+        let repeated = image().value(0.0, 0.75, p);
+        assert_eq_vec3s(repeated, Color::new(0.5, 0.0, 0.0));
+        let clamped = image().with_wrap(WrapMode::Clamp).value(0.0, 0.75, p);
+        assert_eq_vec3s(clamped, Color::zero());
+    }
+
+    #[test]
+    fn png_is_decoded_from_srgb() {
+        let mut bytes = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255, 0, 188, 0, 255, 0]).unwrap();
+        }
+        let texture = ImageTexture::read_png(&bytes[..], true).unwrap();
+        assert_eq!((texture.width(), texture.height()), (2, 1));
+        assert_eq_vec3s(
+            texture.pixels[0],
+            Color::new(1.0, 0.0, srgb_to_linear(188.0 / 255.0)),
+        );
+
+        let linear = ImageTexture::read_png(&bytes[..], false).unwrap();
+        assert_f64_eq(linear.pixels[0].z(), 188.0 / 255.0);
+    }
+
+    #[test]
+    fn unsupported_extension_is_an_error() {
+        match ImageTexture::load("texture.bmp") {
+            Err(TextureError::UnsupportedFormat(ext)) => assert_eq!(ext, "bmp"),
+            _ => panic!("expected an unsupported format error"),
+        }
+    }
+}
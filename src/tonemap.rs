@@ -0,0 +1,268 @@
+use crate::{clamp, luminance, Color};
+use std::{error::Error, fmt, str::FromStr};
+
+// exact sRGB transfer function, from linear [0, 1] to encoded [0, 1]
+pub fn linear_to_srgb(linear: f64) -> f64 {
+    let linear = clamp(linear, 0.0, 1.0);
+    if linear <= 0.003_130_8 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// inverse of linear_to_srgb, e.g. for 8 bit image textures
+pub fn srgb_to_linear(encoded: f64) -> f64 {
+    let encoded = clamp(encoded, 0.0, 1.0);
+    if encoded <= 0.040_45 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// compresses unbounded scene radiance into the displayable [0, 1] range
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneMapOperator {
+    // no compression, everything above 1 clips to white
+    Clamp,
+    // L / (1 + L) on luminance, which never quite reaches white
+    Reinhard,
+    // Reinhard, scaled so that luminance `white` maps exactly to 1
+    ExtendedReinhard { white: f64 },
+    // Krzysztof Narkowicz's fit of the ACES filmic reference curve
+    Aces,
+    // John Hable's Uncharted 2 filmic curve
+    Hable,
+}
+
+impl ToneMapOperator {
+    fn apply(self, color: Color) -> Color {
+        match self {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMapOperator::ExtendedReinhard { white } => {
+                scale_luminance(color, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            ToneMapOperator::Aces => map_components(color, |x| {
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }),
+            ToneMapOperator::Hable => {
+                // linear white point of the curve, and the exposure bias of
+                // Hable's reference implementation
+                const WHITE: f64 = 11.2;
+                const EXPOSURE_BIAS: f64 = 2.0;
+                map_components(color, |x| {
+                    hable_partial(EXPOSURE_BIAS * x) / hable_partial(WHITE)
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseToneMapError(String);
+
+impl fmt::Display for ParseToneMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown tone mapping '{}', expected clamp, reinhard, reinhard:<white>, aces or hable",
+            self.0
+        )
+    }
+}
+
+impl Error for ParseToneMapError {}
+
+// operators by name (case-insensitive), e.g. from a command line or the
+// environment; extended Reinhard is written reinhard:<white>
+impl FromStr for ToneMapOperator {
+    type Err = ParseToneMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase();
+        let operator = match name.as_str() {
+            "clamp" => ToneMapOperator::Clamp,
+            "reinhard" => ToneMapOperator::Reinhard,
+            "aces" => ToneMapOperator::Aces,
+            "hable" => ToneMapOperator::Hable,
+            _ => match name.strip_prefix("reinhard:").map(str::parse::<f64>) {
+                Some(Ok(white)) if white > 0.0 => ToneMapOperator::ExtendedReinhard { white },
+                _ => return Err(ParseToneMapError(s.to_string())),
+            },
+        };
+        Ok(operator)
+    }
+}
+
+fn hable_partial(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn map_components(color: Color, f: impl Fn(f64) -> f64) -> Color {
+    Color::new(f(color.x()), f(color.y()), f(color.z()))
+}
+
+// maps luminance alone, keeping hue and saturation
+fn scale_luminance(color: Color, f: impl Fn(f64) -> f64) -> Color {
+    let l = luminance(color);
+    if l <= 0.0 {
+        return Color::zero();
+    }
+    color * (f(l) / l)
+}
+
+// turns linear framebuffer radiance into sRGB encoded display values
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ToneMapping {
+    operator: ToneMapOperator,
+    exposure: f64, // in stops (EV)
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator) -> Self {
+        Self {
+            operator,
+            exposure: 0.0,
+        }
+    }
+
+    // brightens (positive) or darkens (negative) by this many stops before
+    // the operator is applied
+    pub fn with_exposure(mut self, exposure: f64) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    // display color with components in [0, 1], before sRGB encoding
+    pub fn tone_map(&self, linear: Color) -> Color {
+        let exposed = linear * 2_f64.powf(self.exposure);
+        // NaNs from a bad sample shouldn't poison the output
+        let exposed = map_components(exposed, |c| if c.is_nan() { 0.0 } else { c.max(0.0) });
+        map_components(self.operator.apply(exposed), |c| clamp(c, 0.0, 1.0))
+    }
+
+    // tone maps and sRGB encodes, with components in [0, 1]
+    pub fn encode(&self, linear: Color) -> Color {
+        map_components(self.tone_map(linear), linear_to_srgb)
+    }
+
+    pub fn encode_u8(&self, linear: Color) -> [u8; 3] {
+        let encoded = self.encode(linear);
+        [0, 1, 2].map(|i| (encoded[i] * 255.0).round() as u8)
+    }
+
+    pub fn encode_u16(&self, linear: Color) -> [u16; 3] {
+        let encoded = self.encode(linear);
+        [0, 1, 2].map(|i| (encoded[i] * 65535.0).round() as u16)
+    }
+}
+
+impl Default for ToneMapping {
+    // plain clipping, like the book
+    fn default() -> Self {
+        Self::new(ToneMapOperator::Clamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    fn grey(value: f64) -> Color {
+        Color::new(value, value, value)
+    }
+
+    #[test]
+    fn srgb_transfer_function() {
+        assert_f64_eq(linear_to_srgb(0.0), 0.0);
+        assert_f64_eq(linear_to_srgb(1.0), 1.0);
+        assert_f64_eq(linear_to_srgb(0.002), 0.02584);
+        assert!((linear_to_srgb(0.5) - 0.735_357).abs() < 1e-6);
+        assert!((linear_to_srgb(0.18) - 0.461_356).abs() < 1e-6);
+    }
+
+    #[test]
+    fn srgb_round_trips() {
+        for i in 0..=20 {
+            let linear = i as f64 / 20.0;
+            assert_f64_eq(srgb_to_linear(linear_to_srgb(linear)), linear);
+        }
+    }
+
+    #[test]
+    fn reinhard_halves_luminance_one() {
+        let mapping = ToneMapping::new(ToneMapOperator::Reinhard);
+        assert_eq_vec3s(mapping.tone_map(grey(1.0)), grey(0.5));
+    }
+
+    #[test]
+    fn reinhard_preserves_hue() {
+        let mapping = ToneMapping::new(ToneMapOperator::Reinhard);
+        let mapped = mapping.tone_map(Color::new(0.8, 0.4, 0.2));
+        assert_f64_eq(mapped.x() / mapped.y(), 2.0);
+        assert_f64_eq(mapped.y() / mapped.z(), 2.0);
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_to_one() {
+        let mapping = ToneMapping::new(ToneMapOperator::ExtendedReinhard { white: 8.0 });
+        assert_eq_vec3s(mapping.tone_map(grey(8.0)), grey(1.0));
+        assert!(mapping.tone_map(grey(1.0)).x() > 0.5);
+    }
+
+    #[test]
+    fn filmic_curves_are_monotonic_and_bounded() {
+        for &operator in &[ToneMapOperator::Aces, ToneMapOperator::Hable] {
+            let mapping = ToneMapping::new(operator);
+            assert!(mapping.tone_map(grey(0.0)).x().abs() < 1e-2);
+            let mut previous = -1.0;
+            for i in 0..100 {
+                let mapped = mapping.tone_map(grey(i as f64 * 0.25)).x();
+                assert!(mapped >= previous && mapped <= 1.0);
+                previous = mapped;
+            }
+            // bright highlights roll off instead of clipping straight away
+            assert!(mapping.tone_map(grey(1.0)).x() < 1.0);
+        }
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let mapping = ToneMapping::default().with_exposure(-1.0);
+        assert_eq_vec3s(mapping.tone_map(grey(0.5)), grey(0.25));
+        let mapping = ToneMapping::default().with_exposure(2.0);
+        assert_eq_vec3s(mapping.tone_map(grey(0.125)), grey(0.5));
+    }
+
+    #[test]
+    fn clamp_clips_and_handles_nan() {
+        let mapping = ToneMapping::default();
+        assert_eq_vec3s(
+            mapping.tone_map(Color::new(4.0, -1.0, f64::NAN)),
+            Color::new(1.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn operators_parse_by_name() {
+        assert_eq!("ACES".parse(), Ok(ToneMapOperator::Aces));
+        assert_eq!(" hable ".parse(), Ok(ToneMapOperator::Hable));
+        assert_eq!(
+            "reinhard:4".parse(),
+            Ok(ToneMapOperator::ExtendedReinhard { white: 4.0 })
+        );
+        assert!("reinhard:-1".parse::<ToneMapOperator>().is_err());
+        assert!("filmic".parse::<ToneMapOperator>().is_err());
+    }
+
+    #[test]
+    fn quantisation() {
+        let mapping = ToneMapping::default();
+        assert_eq!(mapping.encode_u8(Color::new(0.0, 0.5, 1.0)), [0, 188, 255]);
+        assert_eq!(mapping.encode_u16(grey(1.0)), [65535; 3]);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::quadric::{angle_around_y, solve_quadratic, NearestHit};
+use crate::{Aabb, HitRecord, Hittable, Material, Point3, Ray, Vec3};
+use std::{f64::consts::PI, sync::Arc};
+
+// real roots of x^3 + a x^2 + b x + c = 0
+fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    if r * r < q * q * q {
+        // three real roots, by the trigonometric method
+        let theta = (r / (q * q * q).sqrt()).acos();
+        let scale = -2.0 * q.sqrt();
+        return (0..3)
+            .map(|k| scale * ((theta + 2.0 * PI * k as f64) / 3.0).cos() - a / 3.0)
+            .collect();
+    }
+    let big = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+    let small = if big != 0.0 { q / big } else { 0.0 };
+    vec![big + small - a / 3.0]
+}
+
+// real roots of x^4 + a x^3 + b x^2 + c x + d = 0 by Ferrari's method, each
+// polished with a few newton steps since the closed form loses precision
+fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // depressed quartic y^4 + p y^2 + q y + r = 0, with x = y - a / 4
+    let shift = a / 4.0;
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut roots = Vec::with_capacity(4);
+    let push_quadratic = |qa: f64, qb: f64, qc: f64, roots: &mut Vec<f64>| {
+        if let Some((y0, y1)) = solve_quadratic(qa, qb, qc) {
+            roots.push(y0);
+            roots.push(y1);
+        }
+    };
+    if q.abs() < 1e-12 {
+        // biquadratic: solve for y^2
+        if let Some((z0, z1)) = solve_quadratic(1.0, p, r) {
+            for &z in &[z0, z1] {
+                if z >= 0.0 {
+                    roots.push(z.sqrt());
+                    roots.push(-z.sqrt());
+                }
+            }
+        }
+    } else {
+        // a positive root m of the resolvent cubic splits the quartic into
+        // two quadratics; one always exists since the cubic is -q^2 at 0
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .fold(0.0, f64::max);
+        if m <= 0.0 {
+            return roots;
+        }
+        let s = (2.0 * m).sqrt();
+        push_quadratic(1.0, s, p / 2.0 + m - q / (2.0 * s), &mut roots);
+        push_quadratic(1.0, -s, p / 2.0 + m + q / (2.0 * s), &mut roots);
+    }
+
+    for x in roots.iter_mut() {
+        *x -= shift;
+        for _ in 0..3 {
+            let f = (((*x + a) * *x + b) * *x + c) * *x + d;
+            let df = ((4.0 * *x + 3.0 * a) * *x + 2.0 * b) * *x + c;
+            if df == 0.0 {
+                break;
+            }
+            *x -= f / df;
+        }
+    }
+    roots
+}
+
+// torus around the y axis, centered on the origin: a tube of radius minor
+// swept around a circle of radius major in the xz plane. u goes around the
+// y axis like on a Sphere, v around the tube starting from its outer edge
+pub struct Torus {
+    major: f64,
+    minor: f64,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(major: f64, minor: f64, mat_ptr: Arc<dyn Material>) -> Self {
+        Self {
+            major,
+            minor,
+            mat_ptr,
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let length = r.direction().length();
+        let d = r.direction() / length;
+        // solve from the point nearest the center, which keeps the
+        // coefficients small for rays from far away
+        let t_closest = -Vec3::dot(r.origin(), d);
+        let o = r.origin() + t_closest * d;
+        let outer = self.major + self.minor;
+        if o.length_squared() > outer * outer {
+            return None;
+        }
+
+        let major2 = self.major * self.major;
+        let e = o.length_squared() - major2 - self.minor * self.minor;
+        let f = Vec3::dot(o, d);
+        let roots = solve_quartic(
+            4.0 * f,
+            2.0 * e + 4.0 * f * f + 4.0 * major2 * d.y() * d.y(),
+            4.0 * f * e + 8.0 * major2 * o.y() * d.y(),
+            e * e - 4.0 * major2 * (self.minor * self.minor - o.y() * o.y()),
+        );
+
+        let mut nearest = NearestHit::new(t_min, t_max);
+        for s in roots {
+            let t = (t_closest + s) / length;
+            if !nearest.in_range(t) {
+                continue;
+            }
+            let p = r.at(t);
+            let alpha = p.length_squared() - major2 - self.minor * self.minor;
+            let normal = Vec3::new(p.x() * alpha, p.y() * (alpha + 2.0 * major2), p.z() * alpha);
+            let radial = (p.x() * p.x() + p.z() * p.z()).sqrt() - self.major;
+            let around_tube = p.y().atan2(radial).rem_euclid(2.0 * PI) / (2.0 * PI);
+            nearest.offer(t, normal, (angle_around_y(p), around_tube));
+        }
+        nearest.into_record(r, &self.mat_ptr)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let outer = self.major + self.minor;
+        Some(Aabb::new(
+            Point3::new(-outer, -self.minor, -outer),
+            Point3::new(outer, self.minor, outer),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    fn torus() -> Torus {
+        Torus::new(2.0, 0.5, grey_lambertian())
+    }
+
+    fn sorted(mut roots: Vec<f64>) -> Vec<f64> {
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        roots
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        let roots = sorted(solve_quartic(-10.0, 35.0, -50.0, 24.0));
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip(&[1.0, 2.0, 3.0, 4.0]) {
+            assert_f64_eq(*root, *expected);
+        }
+        // (x^2 - 1)(x^2 + 1) has two real roots
+        let roots = sorted(solve_quartic(0.0, 0.0, 0.0, -1.0));
+        assert_eq!(roots.len(), 2);
+        assert_f64_eq(roots[0], -1.0);
+        assert_f64_eq(roots[1], 1.0);
+        // (x^2 + 1)(x^2 + 4) has none
+        assert!(solve_quartic(0.0, 5.0, 0.0, 4.0).is_empty());
+    }
+
+    #[test]
+    fn ray_through_the_hole_crosses_the_tube_four_times() {
+        let torus = torus();
+        let r = Ray::new(Point3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = torus.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(-2.5, 0.0, 0.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!(rec.is_front_face);
+        assert_f64_eq(rec.v, 0.0);
+
+        // just past the first crossing, the ray is inside the tube
+        let rec = torus.hit(r, rec.t + 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(-1.5, 0.0, 0.0));
+        assert!(!rec.is_front_face);
+        assert_eq_vec3s(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+
+        let rec = torus.hit(r, rec.t + 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(1.5, 0.0, 0.0));
+        assert!(rec.is_front_face);
+    }
+
+    #[test]
+    fn ray_down_the_axis_misses() {
+        let r = Ray::new(Point3::new(0.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(torus().hit(r, 0.001, f64::INFINITY).is_none());
+        // but hits the top of the tube off axis, far away and unnormalized
+        let r = Ray::new(Point3::new(0.0, 1e4, 2.0), Vec3::new(0.0, -3.0, 0.0));
+        let rec = torus().hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(0.0, 0.5, 2.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert_f64_eq(rec.v, 0.25);
+    }
+
+    #[test]
+    fn hits_lie_on_the_surface() {
+        let torus = torus();
+        let mut rng = crate::Pcg32::new(4);
+        for _ in 0..500 {
+            let origin = 8.0 * crate::sample_uniform_sphere((rng.rand_f64(), rng.rand_f64()));
+            let target = Vec3::rand_in_range(&mut rng, -2.0, 2.0);
+            if let Some(rec) = torus.hit(Ray::new(origin, target - origin), 0.001, f64::INFINITY) {
+                let p = rec.point;
+                let radial = (p.x() * p.x() + p.z() * p.z()).sqrt() - 2.0;
+                assert!((radial * radial + p.y() * p.y() - 0.25).abs() < 1e-6);
+                assert!(Vec3::dot(rec.normal, target - origin) < 0.0);
+            }
+        }
+    }
+}
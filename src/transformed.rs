@@ -0,0 +1,264 @@
+use crate::{Aabb, HitRecord, Hittable, Matrix4, Point3, Ray, Vec3};
+
+// any hittable placed in the world by an affine transform. rays are moved
+// into the object's own space for intersection, and the hit brought back,
+// with normals transformed by the inverse transpose so they stay
+// perpendicular under non-uniform scaling
+pub struct Transformed<H: Hittable> {
+    object: H,
+    object_to_world: Matrix4,
+    world_to_object: Matrix4,
+}
+
+impl<H: Hittable> Transformed<H> {
+    pub fn new(object: H) -> Self {
+        Self {
+            object,
+            object_to_world: Matrix4::identity(),
+            world_to_object: Matrix4::identity(),
+        }
+    }
+
+    // each of the builders below applies its transform after the ones before
+    // it, so e.g. scale, then rotate, then translate reads in order
+    fn then(mut self, transform: Matrix4, inverse: Matrix4) -> Self {
+        self.object_to_world = transform * self.object_to_world;
+        self.world_to_object = self.world_to_object * inverse;
+        self
+    }
+
+    pub fn translate(self, offset: Vec3) -> Self {
+        self.then(Matrix4::translation(offset), Matrix4::translation(-offset))
+    }
+
+    // counterclockwise by `degrees` around `axis` through the origin
+    pub fn rotate(self, axis: Vec3, degrees: f64) -> Self {
+        self.then(
+            Matrix4::rotation(axis, degrees),
+            Matrix4::rotation(axis, -degrees),
+        )
+    }
+
+    pub fn scale(self, factors: Vec3) -> Self {
+        assert!(
+            factors.x() != 0.0 && factors.y() != 0.0 && factors.z() != 0.0,
+            "scale factors must be non-zero"
+        );
+        self.then(
+            Matrix4::scaling(factors),
+            Matrix4::scaling(Vec3::new(
+                1.0 / factors.x(),
+                1.0 / factors.y(),
+                1.0 / factors.z(),
+            )),
+        )
+    }
+
+    // any other affine transform, which must be invertible
+    pub fn transform(self, transform: Matrix4) -> Self {
+        let inverse = transform.inverse().expect("transform must be invertible");
+        self.then(transform, inverse)
+    }
+
+    pub fn object(&self) -> &H {
+        &self.object
+    }
+
+    pub fn object_to_world(&self) -> Matrix4 {
+        self.object_to_world
+    }
+
+    pub fn world_to_object(&self) -> Matrix4 {
+        self.world_to_object
+    }
+
+    // the direction isn't renormalized, so t means the same in both spaces
+    fn object_ray(&self, r: Ray) -> Ray {
+        Ray::new(
+            self.world_to_object.transform_point(r.origin()),
+            self.world_to_object.transform_vector(r.direction()),
+        )
+        .with_time(r.time())
+    }
+
+    // brings a hit of the object ray back to the world ray r. local_point
+    // stays in object space
+    fn world_hit(&self, r: Ray, mut rec: HitRecord) -> HitRecord {
+        rec.point = r.at(rec.t);
+        // the normal stays on the ray's side, as the inverse transpose keeps
+        // dot products with transformed directions unchanged
+        let normal = self
+            .world_to_object
+            .transpose()
+            .transform_vector(rec.normal);
+        rec.normal = Vec3::unit(normal);
+        rec
+    }
+}
+
+impl<H: Hittable> Hittable for Transformed<H> {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let rec = self.object.hit(self.object_ray(r), t_min, t_max)?;
+        Some(self.world_hit(r, rec))
+    }
+
+    fn all_hits(&self, r: Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        self.object
+            .all_hits(self.object_ray(r), t_min, t_max)
+            .into_iter()
+            .map(|rec| self.world_hit(r, rec))
+            .collect()
+    }
+
+    // box around the transformed corners of the object's box
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bbox = self.object.bounding_box()?;
+        let (min, max) = (bbox.min(), bbox.max());
+        let corner = |i: usize| {
+            Point3::new(
+                if i & 1 == 0 { min.x() } else { max.x() },
+                if i & 2 == 0 { min.y() } else { max.y() },
+                if i & 4 == 0 { min.z() } else { max.z() },
+            )
+        };
+        let first = self.object_to_world.transform_point(corner(0));
+        let world_box = (1..8).fold(Aabb::new(first, first), |world_box, i| {
+            let p = self.object_to_world.transform_point(corner(i));
+            Aabb::surrounding_box(world_box, Aabb::new(p, p))
+        });
+        Some(world_box)
+    }
+
+    fn can_sample(&self) -> bool {
+        self.object.can_sample()
+    }
+
+    // light sampling happens in object space. world_to_object maps a unit
+    // direction w through its linear part A to A w, which changes solid
+    // angles by |det A| / |A w|^3
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        let object_direction = self.world_to_object.transform_vector(Vec3::unit(direction));
+        let pdf = self.object.pdf_value(
+            self.world_to_object.transform_point(origin),
+            object_direction,
+            time,
+        );
+        pdf * self.world_to_object.linear_determinant().abs() / object_direction.length().powi(3)
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), time: f64) -> Vec3 {
+        let object_origin = self.world_to_object.transform_point(origin);
+        self.object_to_world
+            .transform_vector(self.object.random(object_origin, u, time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{Color, Lambertian, Quad, Sphere};
+    use std::sync::Arc;
+
+    fn unit_sphere() -> Sphere {
+        Sphere::new(
+            Point3::zero(),
+            1.0,
+            Arc::new(Lambertian::new(Color::zero())),
+        )
+    }
+
+    #[test]
+    fn translated_sphere_moves() {
+        let sphere = Transformed::new(unit_sphere()).translate(Vec3::new(0.0, 0.0, -5.0));
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        let rec = sphere.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_f64_eq(rec.t, 4.0);
+        assert_eq_vec3s(rec.point, Point3::new(0.0, 0.0, -4.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.is_front_face);
+    }
+
+    #[test]
+    fn scaled_sphere_is_an_ellipsoid_with_correct_normals() {
+        let ellipsoid = Transformed::new(unit_sphere()).scale(Vec3::new(2.0, 1.0, 1.0));
+        let r = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = ellipsoid.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(-2.0, 0.0, 0.0));
+
+        // on x^2/4 + y^2 = 1 the normal is along the gradient (x/4, y, 0)
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+        let rec = ellipsoid.hit(r, 0.001, f64::INFINITY).unwrap();
+        let p = rec.point;
+        assert_f64_eq(p.x() * p.x() / 4.0 + p.y() * p.y(), 1.0);
+        // from inside, the normal faces back along the ray
+        let gradient = Vec3::unit(Vec3::new(p.x() / 4.0, p.y(), 0.0));
+        assert_eq_vec3s(rec.normal, -gradient);
+        assert!(!rec.is_front_face);
+    }
+
+    #[test]
+    fn transforms_compose_in_order() {
+        let sphere = Transformed::new(unit_sphere())
+            .scale(Vec3::new(1.0, 3.0, 1.0))
+            .rotate(Vec3::new(0.0, 0.0, 1.0), 90.0)
+            .translate(Vec3::new(10.0, 0.0, 0.0));
+        // stretched along y, then turned to lie along x, then moved
+        let bbox = sphere.bounding_box().unwrap();
+        assert_eq_vec3s(bbox.min(), Point3::new(7.0, -1.0, -1.0));
+        assert_eq_vec3s(bbox.max(), Point3::new(13.0, 1.0, 1.0));
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = sphere.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(7.0, 0.0, 0.0));
+        assert_eq_vec3s(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+
+        let same = Transformed::new(unit_sphere()).transform(sphere.object_to_world());
+        let rec = same.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_eq_vec3s(rec.point, Point3::new(7.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn rotated_box_grows() {
+        let sphere = Transformed::new(unit_sphere()).rotate(Vec3::new(0.0, 1.0, 0.0), 45.0);
+        let bbox = sphere.bounding_box().unwrap();
+        let half_diagonal = 2_f64.sqrt();
+        assert_f64_eq(bbox.max().x(), half_diagonal);
+        assert_f64_eq(bbox.max().y(), 1.0);
+    }
+
+    #[test]
+    fn transformed_lights_are_sampled_in_world_space() {
+        let material = Arc::new(Lambertian::new(Color::zero()));
+        let (q, u, v) = (
+            Point3::new(-0.5, 0.0, -0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        let light = Transformed::new(Quad::new(q, u, v, material.clone()))
+            .scale(Vec3::new(2.0, 1.0, 0.5))
+            .rotate(Vec3::new(1.0, 0.0, 1.0), 30.0)
+            .translate(Vec3::new(0.0, 4.0, 0.0));
+        // the same parallelogram, built directly in world space
+        let m = light.object_to_world();
+        let world = Quad::new(
+            m.transform_point(q),
+            m.transform_vector(u),
+            m.transform_vector(v),
+            material,
+        );
+
+        for &origin in &[Point3::zero(), Point3::new(1.0, 1.0, -2.0)] {
+            for &sample in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.7)] {
+                let direction = light.random(origin, sample, 0.0);
+                assert_eq_vec3s(direction, world.random(origin, sample, 0.0));
+                let expected = world.pdf_value(origin, direction, 0.0);
+                assert!(expected > 0.0);
+                let pdf = light.pdf_value(origin, direction, 0.0);
+                assert!((pdf - expected).abs() < 1e-9 * expected);
+            }
+        }
+        let away = Vec3::new(0.0, -1.0, 0.0);
+        assert_eq!(light.pdf_value(Point3::zero(), away, 0.0), 0.0);
+    }
+}
@@ -0,0 +1,480 @@
+use crate::{Aabb, HitRecord, Hittable, HittableList, Material, Point3, Ray, Vec3};
+use std::sync::Arc;
+
+pub struct Triangle {
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>, // per-vertex normals for smooth shading
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Triangle {
+    // the front face is the side from which the vertices appear
+    // counter-clockwise
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, mat_ptr: Arc<dyn Material>) -> Self {
+        Self {
+            vertices: [v0, v1, v2],
+            normals: None,
+            mat_ptr,
+        }
+    }
+
+    // triangle whose shading normal is interpolated from the vertex normals
+    pub fn with_normals(
+        vertices: [Point3; 3],
+        normals: [Vec3; 3],
+        mat_ptr: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            vertices,
+            normals: Some(normals),
+            mat_ptr,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_triangle(
+            r,
+            t_min,
+            t_max,
+            self.vertices,
+            self.normals,
+            None,
+            &self.mat_ptr,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_box(self.vertices))
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, _time: f64) -> f64 {
+        triangle_pdf_value(self.vertices, origin, direction)
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), _time: f64) -> Vec3 {
+        triangle_random(self.vertices, origin, u)
+    }
+}
+
+// vertex and index buffers shared by all of a mesh's triangles
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    indices: Vec<[usize; 3]>,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl TriangleMesh {
+    // each entry of indices names the three positions (and normals, if any)
+    // of one triangle
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Option<Vec<Vec3>>,
+        indices: Vec<[usize; 3]>,
+        mat_ptr: Arc<dyn Material>,
+    ) -> Self {
+        if let Some(normals) = &normals {
+            assert_eq!(
+                normals.len(),
+                positions.len(),
+                "a mesh needs one normal per vertex"
+            );
+        }
+        assert!(
+            indices.iter().flatten().all(|&i| i < positions.len()),
+            "mesh index out of bounds"
+        );
+        Self {
+            positions,
+            normals,
+            uvs: None,
+            indices,
+            mat_ptr,
+        }
+    }
+
+    // attaches per-vertex texture coordinates
+    pub fn with_uvs(mut self, uvs: Vec<(f64, f64)>) -> Self {
+        assert_eq!(
+            uvs.len(),
+            self.positions.len(),
+            "a mesh needs one uv per vertex"
+        );
+        self.uvs = Some(uvs);
+        self
+    }
+
+    pub fn positions(&self) -> &[Point3] {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> Option<&[Vec3]> {
+        self.normals.as_deref()
+    }
+
+    pub fn uvs(&self) -> Option<&[(f64, f64)]> {
+        self.uvs.as_deref()
+    }
+
+    pub fn indices(&self) -> &[[usize; 3]] {
+        &self.indices
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    // one hittable per triangle, all referencing this mesh's buffers;
+    // usually handed straight to BvhNode::new
+    pub fn triangles(self: Arc<Self>) -> HittableList {
+        let mut list = HittableList::default();
+        for index in 0..self.len() {
+            list.add(Arc::new(MeshTriangle {
+                mesh: self.clone(),
+                index,
+            }));
+        }
+        list
+    }
+
+    fn vertices(&self, index: usize) -> [Point3; 3] {
+        let [i0, i1, i2] = self.indices[index];
+        [self.positions[i0], self.positions[i1], self.positions[i2]]
+    }
+
+    fn vertex_normals(&self, index: usize) -> Option<[Vec3; 3]> {
+        let [i0, i1, i2] = self.indices[index];
+        self.normals
+            .as_ref()
+            .map(|normals| [normals[i0], normals[i1], normals[i2]])
+    }
+
+    fn vertex_uvs(&self, index: usize) -> Option<[(f64, f64); 3]> {
+        let [i0, i1, i2] = self.indices[index];
+        self.uvs.as_ref().map(|uvs| [uvs[i0], uvs[i1], uvs[i2]])
+    }
+}
+
+pub struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    index: usize,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_triangle(
+            r,
+            t_min,
+            t_max,
+            self.mesh.vertices(self.index),
+            self.mesh.vertex_normals(self.index),
+            self.mesh.vertex_uvs(self.index),
+            &self.mesh.mat_ptr,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_box(self.mesh.vertices(self.index)))
+    }
+
+    fn can_sample(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, _time: f64) -> f64 {
+        triangle_pdf_value(self.mesh.vertices(self.index), origin, direction)
+    }
+
+    fn random(&self, origin: Point3, u: (f64, f64), _time: f64) -> Vec3 {
+        triangle_random(self.mesh.vertices(self.index), origin, u)
+    }
+}
+
+// Möller–Trumbore intersection, returning the ray parameter and the
+// barycentric coordinates (b1, b2) of the hit, weighting vertices 1 and 2
+pub fn intersect_triangle(
+    r: Ray,
+    t_min: f64,
+    t_max: f64,
+    [v0, v1, v2]: [Point3; 3],
+) -> Option<(f64, f64, f64)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let p = Vec3::cross(r.direction(), edge2);
+    let det = Vec3::dot(edge1, p);
+    // ray is parallel to the triangle's plane
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let s = r.origin() - v0;
+    let b1 = Vec3::dot(s, p) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let q = Vec3::cross(s, edge1);
+    let b2 = Vec3::dot(r.direction(), q) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = Vec3::dot(edge2, q) * inv_det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+fn hit_triangle(
+    r: Ray,
+    t_min: f64,
+    t_max: f64,
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    mat_ptr: &Arc<dyn Material>,
+) -> Option<HitRecord> {
+    let (t, b1, b2) = intersect_triangle(r, t_min, t_max, vertices)?;
+    let [v0, v1, v2] = vertices;
+    let mut geometric_normal = Vec3::unit(Vec3::cross(v1 - v0, v2 - v0));
+    let shading_normal =
+        normals.map(|[n0, n1, n2]| Vec3::unit((1.0 - b1 - b2) * n0 + b1 * n1 + b2 * n2));
+    // vertex normals decide which side is outside, whatever the winding
+    if let Some(shading_normal) = shading_normal {
+        if Vec3::dot(geometric_normal, shading_normal) < 0.0 {
+            geometric_normal = -geometric_normal;
+        }
+    }
+
+    // without vertex uvs, the barycentrics map the triangle onto half of the
+    // unit square
+    let (u, v) = match uvs {
+        Some([uv0, uv1, uv2]) => (
+            (1.0 - b1 - b2) * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            (1.0 - b1 - b2) * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+        ),
+        None => (b1, b2),
+    };
+
+    let point = r.at(t);
+    let mut hit_rec = HitRecord {
+        t,
+        point,
+        local_point: point,
+        normal: geometric_normal,
+        u,
+        v,
+        is_front_face: true,
+        mat_ptr: mat_ptr.clone(),
+    };
+    hit_rec.set_face_normal(r, geometric_normal);
+    if let Some(shading_normal) = shading_normal {
+        hit_rec.normal = if hit_rec.is_front_face {
+            shading_normal
+        } else {
+            -shading_normal
+        };
+    }
+    Some(hit_rec)
+}
+
+// points are sampled uniformly by area, so the density per unit solid angle
+// is distance^2 / (|cos| * area)
+fn triangle_pdf_value(vertices: [Point3; 3], origin: Point3, direction: Vec3) -> f64 {
+    let t = match intersect_triangle(Ray::new(origin, direction), 0.001, f64::INFINITY, vertices) {
+        Some((t, _, _)) => t,
+        None => return 0.0,
+    };
+    let [v0, v1, v2] = vertices;
+    let doubled_area_normal = Vec3::cross(v1 - v0, v2 - v0);
+    let doubled_area = doubled_area_normal.length();
+    let cosine =
+        Vec3::dot(direction, doubled_area_normal).abs() / (direction.length() * doubled_area);
+    let distance_squared = t * t * direction.length_squared();
+    distance_squared / (cosine * 0.5 * doubled_area)
+}
+
+fn triangle_random([v0, v1, v2]: [Point3; 3], origin: Point3, u: (f64, f64)) -> Vec3 {
+    // uniform barycentrics, by folding the unit square onto the triangle
+    let su0 = u.0.sqrt();
+    let point = (1.0 - su0) * v0 + su0 * (1.0 - u.1) * v1 + su0 * u.1 * v2;
+    point - origin
+}
+
+fn triangle_box([v0, v1, v2]: [Point3; 3]) -> Aabb {
+    // pad the box so that axis-aligned triangles don't get a flat box
+    let padding = Vec3::new(1e-4, 1e-4, 1e-4);
+    let bbox = Aabb::surrounding_box(Aabb::new(v0, v0), Aabb::new(v1, v1));
+    let bbox = Aabb::surrounding_box(bbox, Aabb::new(v2, v2));
+    Aabb::new(bbox.min() - padding, bbox.max() + padding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::BvhNode;
+
+    // unit right triangle in the z = -1 plane, facing +z
+    fn vertices() -> [Point3; 3] {
+        [
+            Point3::new(0.0, 0.0, -1.0),
+            Point3::new(1.0, 0.0, -1.0),
+            Point3::new(0.0, 1.0, -1.0),
+        ]
+    }
+
+    #[test]
+    fn light_samples_land_on_the_triangle() {
+        let [v0, v1, v2] = vertices();
+        let triangle = Triangle::new(v0, v1, v2, grey_lambertian());
+        let origin = Point3::zero();
+        for &u in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.7)] {
+            let direction = triangle.random(origin, u, 0.0);
+            assert!(triangle
+                .hit(Ray::new(origin, direction), 0.001, 1.001)
+                .is_some());
+            // the density doesn't depend on the direction's length
+            assert_f64_eq(
+                triangle.pdf_value(origin, direction, 0.0),
+                triangle.pdf_value(origin, 3.0 * direction, 0.0),
+            );
+        }
+        // straight on, at distance 1, the density is 1 / area
+        let straight = Vec3::new(0.0, 0.0, -1.0);
+        assert_f64_eq(
+            triangle.pdf_value(Point3::new(0.25, 0.25, 0.0), straight, 0.0),
+            2.0,
+        );
+        assert_eq!(
+            triangle.pdf_value(origin, Vec3::new(0.0, 0.0, 1.0), 0.0),
+            0.0
+        );
+    }
+
+    #[test]
+    fn ray_hits_front_face() {
+        let [v0, v1, v2] = vertices();
+        let triangle = Triangle::new(v0, v1, v2, grey_lambertian());
+        let r = Ray::new(Point3::new(0.25, 0.25, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = triangle.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_f64_eq(rec.t, 1.0);
+        assert!(rec.is_front_face);
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn ray_hits_back_face() {
+        let [v0, v1, v2] = vertices();
+        let triangle = Triangle::new(v0, v1, v2, grey_lambertian());
+        let r = Ray::new(Point3::new(0.25, 0.25, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = triangle.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert!(!rec.is_front_face);
+        assert_eq_vec3s(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn ray_outside_edges_misses() {
+        let [v0, v1, v2] = vertices();
+        let triangle = Triangle::new(v0, v1, v2, grey_lambertian());
+        let r = Ray::new(Point3::new(0.75, 0.75, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(triangle.hit(r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn barycentrics_weight_vertices() {
+        let r = Ray::new(Point3::new(0.2, 0.3, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let (_, b1, b2) = intersect_triangle(r, 0.001, f64::INFINITY, vertices()).unwrap();
+        assert_f64_eq(b1, 0.2);
+        assert_f64_eq(b2, 0.3);
+    }
+
+    #[test]
+    fn vertex_normals_are_interpolated() {
+        let normals = [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::unit(Vec3::new(1.0, 0.0, 1.0)),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        let triangle = Triangle::with_normals(vertices(), normals, grey_lambertian());
+        let r = Ray::new(Point3::new(0.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = triangle.hit(r, 0.001, f64::INFINITY).unwrap();
+        let expected = Vec3::unit(0.5 * normals[0] + 0.5 * normals[1]);
+        assert_eq_vec3s(rec.normal, expected);
+    }
+
+    #[test]
+    fn mesh_uvs_are_interpolated() {
+        let mesh = Arc::new(
+            TriangleMesh::new(
+                vertices().to_vec(),
+                None,
+                vec![[0, 1, 2]],
+                grey_lambertian(),
+            )
+            .with_uvs(vec![(0.0, 0.0), (1.0, 0.0), (0.0, 0.5)]),
+        );
+        let triangles = mesh.triangles();
+        let r = Ray::new(Point3::new(0.5, 0.25, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = triangles.hit(r, 0.001, f64::INFINITY).unwrap();
+        assert_f64_eq(rec.u, 0.5);
+        assert_f64_eq(rec.v, 0.125);
+
+        // plain triangles fall back to the barycentrics
+        let [v0, v1, v2] = vertices();
+        let rec = Triangle::new(v0, v1, v2, grey_lambertian())
+            .hit(r, 0.001, f64::INFINITY)
+            .unwrap();
+        assert_f64_eq(rec.u, 0.5);
+        assert_f64_eq(rec.v, 0.25);
+    }
+
+    #[test]
+    fn mesh_triangles_share_buffers() {
+        // a unit square in the z = -1 plane made of two triangles
+        let positions = vec![
+            Point3::new(0.0, 0.0, -1.0),
+            Point3::new(1.0, 0.0, -1.0),
+            Point3::new(1.0, 1.0, -1.0),
+            Point3::new(0.0, 1.0, -1.0),
+        ];
+        let mesh = Arc::new(TriangleMesh::new(
+            positions,
+            None,
+            vec![[0, 1, 2], [0, 2, 3]],
+            grey_lambertian(),
+        ));
+        let triangles = mesh.clone().triangles();
+        assert_eq!(triangles.objects().len(), 2);
+        // one reference from us, one from each triangle
+        assert_eq!(Arc::strong_count(&mesh), 3);
+
+        let bvh = BvhNode::new(triangles);
+        for &(x, y) in &[(0.75, 0.25), (0.25, 0.75)] {
+            let r = Ray::new(Point3::new(x, y, 0.0), Vec3::new(0.0, 0.0, -1.0));
+            assert_f64_eq(bvh.hit(r, 0.001, f64::INFINITY).unwrap().t, 1.0);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn mesh_rejects_out_of_bounds_indices() {
+        TriangleMesh::new(
+            vertices().to_vec(),
+            None,
+            vec![[0, 1, 3]],
+            grey_lambertian(),
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{rand_f64, rand_in_range};
+use crate::Pcg32;
 use std::{
     fmt,
     ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub},
@@ -56,33 +56,33 @@ impl Vec3 {
         vec / vec.length()
     }
 
-    pub fn rand() -> Self {
-        Self::new(rand_f64(), rand_f64(), rand_f64())
+    pub fn rand(rng: &mut Pcg32) -> Self {
+        Self::new(rng.rand_f64(), rng.rand_f64(), rng.rand_f64())
     }
 
-    pub fn rand_in_range(min: f64, max: f64) -> Self {
+    pub fn rand_in_range(rng: &mut Pcg32, min: f64, max: f64) -> Self {
         Self::new(
-            rand_in_range(min, max),
-            rand_in_range(min, max),
-            rand_in_range(min, max),
+            rng.rand_in_range(min, max),
+            rng.rand_in_range(min, max),
+            rng.rand_in_range(min, max),
         )
     }
 
-    pub fn rand_in_unit_sphere() -> Self {
+    pub fn rand_in_unit_sphere(rng: &mut Pcg32) -> Self {
         loop {
-            let vec = Self::rand_in_range(-1.0, 1.0);
+            let vec = Self::rand_in_range(rng, -1.0, 1.0);
             if vec.length_squared() < 1.0 {
                 return vec;
             }
         }
     }
 
-    pub fn rand_unit_vector() -> Self {
-        Self::unit(Self::rand_in_unit_sphere())
+    pub fn rand_unit_vector(rng: &mut Pcg32) -> Self {
+        Self::unit(Self::rand_in_unit_sphere(rng))
     }
 
-    pub fn rand_in_hemisphere(normal: Vec3) -> Self {
-        let vec = Self::rand_in_unit_sphere();
+    pub fn rand_in_hemisphere(rng: &mut Pcg32, normal: Vec3) -> Self {
+        let vec = Self::rand_in_unit_sphere(rng);
         if Self::dot(vec, normal) > 0.0 {
             vec
         } else {
@@ -90,9 +90,13 @@ impl Vec3 {
         }
     }
 
-    pub fn rand_in_unit_disk() -> Self {
+    pub fn rand_in_unit_disk(rng: &mut Pcg32) -> Self {
         loop {
-            let p = Vec3::new(rand_in_range(-1.0, 1.0), rand_in_range(-1.0, 1.0), 0.0);
+            let p = Vec3::new(
+                rng.rand_in_range(-1.0, 1.0),
+                rng.rand_in_range(-1.0, 1.0),
+                0.0,
+            );
             if p.length_squared() < 1.0 {
                 return p;
             }
@@ -254,7 +258,7 @@ mod tests {
         #[should_panic]
         fn invalid_subscript_panics(i in 3..100usize) {
             let v = Vec3::zero();
-            v[i];
+            let _ = v[i];
         }
 
         #[test]